@@ -1,35 +1,89 @@
 use std::collections::BTreeMap;
-use std::path::PathBuf;
-use std::{fmt, io};
+use std::path::{Path, PathBuf};
 
-use fs_err as fs;
+use ns2_stat::config::Config;
 use ns2_stat::input_types::GameStats;
+use ns2_stat::load::{self, ErrorChain, Games, LoadError, LoadOptions, Sources};
+use ns2_stat::GameId;
+use serde::Serialize;
 
-#[derive(Debug)]
-struct JsonParseError {
-    source: serde_json::Error,
-    path: PathBuf,
+/// A game file that could not be loaded. Its games are missing until it is fixed.
+#[derive(Debug, Serialize)]
+pub struct RejectedFile {
+    pub path: PathBuf,
+    /// The error with its causes, separated by `: `.
+    pub error: String,
+    /// The position of a JSON syntax or structure error, starting at 1.
+    pub line: Option<usize>,
+    pub column: Option<usize>,
 }
 
-impl fmt::Display for JsonParseError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "failed to parse file `{}`", self.path.display())
+impl From<&LoadError> for RejectedFile {
+    fn from(err: &LoadError) -> Self {
+        let (line, column) = match err {
+            LoadError::Parse { line, column, .. } => (Some(*line), Some(*column)),
+            _ => (None, None),
+        };
+        Self {
+            path: err.path().to_owned(),
+            error: ErrorChain(err).to_string(),
+            line,
+            column,
+        }
     }
 }
 
-impl std::error::Error for JsonParseError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        Some(&self.source)
+/// Prints the skipped duplicates and the conflicts of a load or an update.
+pub fn report_skipped(duplicates: &[(PathBuf, GameId)], conflicts: &[(GameId, GameId)]) {
+    for (path, original) in duplicates {
+        println!("skipping `{}`: duplicate of `{}`", path.display(), original);
     }
+    for (id, conflict) in conflicts {
+        eprintln!("Warning: `{}` conflicts with `{}`, keeping both", id, conflict);
+    }
+}
+
+/// The result of [`load`].
+pub struct Loaded {
+    pub games: BTreeMap<GameId, GameStats>,
+    /// Only files that failed with [`OnError::Collect`](load::OnError::Collect) in the options.
+    pub rejected: Vec<RejectedFile>,
+    /// To update the games when some files change.
+    pub sources: Sources,
+    /// The configuration that was applied to the games, to apply it to updated games.
+    pub config: Config,
 }
 
-pub fn load<P: Into<PathBuf>>(path: P) -> io::Result<BTreeMap<u32, GameStats>> {
-    fs::read_dir(path)?
-        .map(|entry| {
-            let path = entry?.path();
-            let game = serde_json::from_str::<GameStats>(&fs::read_to_string(&path)?)
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, JsonParseError { source: e, path }))?;
-            Ok((game.round_info.round_date, game))
-        })
-        .collect::<io::Result<_>>()
+/// The configuration for the games of a database, which only has one if it is given explicitly.
+pub fn load_database_config(path: Option<&Path>) -> Result<Config, LoadError> {
+    path.map_or_else(|| Ok(Config::default()), Config::load)
+}
+
+/// Loads all games at the path, applies the configuration and reports skipped duplicates and conflicts.
+///
+/// Files with the same content as an already loaded file are skipped. Files that claim to be the same round
+/// on the same server as another file, but have a different content, are kept and reported as conflicts.
+/// With a cache file in the options, only new and changed files are parsed. The configuration is read again on
+/// every load, so changes to it apply on the next reload.
+pub fn load<P: AsRef<Path>>(path: P, options: &LoadOptions, config: Option<&Path>) -> Result<Loaded, LoadError> {
+    let config = Config::load_or_default(config, path.as_ref())?;
+    let Games {
+        mut games,
+        duplicates,
+        conflicts,
+        rejected,
+        sources,
+    } = load::load_with(path, options)?;
+    report_skipped(&duplicates, &conflicts);
+    let rejected = rejected.iter().map(RejectedFile::from).collect::<Vec<_>>();
+    for file in &rejected {
+        eprintln!("Warning: skipping a file: {}", file.error);
+    }
+    config.apply(&mut games);
+    Ok(Loaded {
+        games,
+        rejected,
+        sources,
+        config,
+    })
 }
@@ -0,0 +1,156 @@
+//! The endpoints for games.
+
+use std::str::FromStr;
+
+use actix_web::body::EitherBody;
+use actix_web::web::{Data, Path, Query};
+use actix_web::{get, HttpResponse, Responder};
+use ns2_stat::filter::GameFilter;
+use ns2_stat::input_types::{GameStats, Location, SteamId};
+use ns2_stat::{summarize_game, GameId, GameSummary};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::store::Store;
+use crate::{internal_error, json_response, not_found, AppData, Page, PageQuery};
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SortKey {
+    /// The round date, and the rest of the ID for games with the same date.
+    #[default]
+    Date,
+    Length,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Order {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Deserialize)]
+struct SortQuery {
+    #[serde(default)]
+    sort: SortKey,
+    #[serde(default)]
+    order: Order,
+}
+
+#[derive(Serialize)]
+struct GamesResponse {
+    #[serde(flatten)]
+    page: Page<GameSummary>,
+    /// The number of games without the filter.
+    unfiltered_total: usize,
+}
+
+#[get("/games")]
+async fn get_games(data: Data<AppData>, filter: Query<GameFilter>, sort: Query<SortQuery>, page: Query<PageQuery>) -> impl Responder {
+    let mut matching = Vec::new();
+    if let Err(err) = data
+        .games
+        .for_each_game(&filter, |id, game| matching.push((id.clone(), game.round_info.round_length)))
+    {
+        return internal_error(err);
+    }
+    if let SortKey::Length = sort.sort {
+        matching.sort_by(|(_, length1), (_, length2)| f32::total_cmp(length1, length2));
+    }
+    if sort.order == Order::Desc {
+        matching.reverse();
+    }
+    let unfiltered_total = match data.games.len() {
+        Ok(total) => total,
+        Err(err) => return internal_error(err),
+    };
+    // only the games in the page are read and summarized
+    let page = Page::new(matching, &page).map(|(id, _)| id);
+    match summarize_games(&data.games, &page.items) {
+        Ok(summaries) => json_response(&GamesResponse {
+            page: page.with_items(summaries),
+            unfiltered_total,
+        }),
+        Err(err) => internal_error(err),
+    }
+}
+
+/// The summaries of the games with the IDs `ids`.
+pub fn summarize_games(games: &Store, ids: &[GameId]) -> Result<Vec<GameSummary>, String> {
+    let mut summaries = Vec::with_capacity(ids.len());
+    games.for_each_id(ids, |id, game| summaries.push(summarize_game(id, game)))?;
+    Ok(summaries)
+}
+
+#[get("/games/latest")]
+async fn get_latest_game(data: Data<AppData>, filter: Query<GameFilter>) -> impl Responder {
+    let mut latest = None;
+    if let Err(err) = data.games.for_each_game(&filter, |id, _| latest = Some(id.clone())) {
+        return internal_error(err);
+    }
+    match summarize_games(&data.games, latest.as_slice()).map(|summaries| summaries.into_iter().next()) {
+        Ok(Some(latest_game)) => json_response(&latest_game),
+        Ok(None) => not_found("there are no matching games".to_owned()),
+        Err(err) => internal_error(err),
+    }
+}
+
+/// Reads the game with the ID `id`, or the response for an unknown or invalid ID. Games that are not genuine are found
+/// as well, since the ID is explicit.
+fn find_game(games: &Store, id: &str) -> Result<(GameId, GameStats), HttpResponse<EitherBody<String>>> {
+    let not_found = || not_found(format!("no game with the ID `{}`", id));
+    let game_id = GameId::from_str(id).map_err(|_| not_found())?;
+    match games.game(&game_id) {
+        Ok(Some(game)) => Ok((game_id, game)),
+        Ok(None) => Err(not_found()),
+        Err(err) => Err(internal_error(err)),
+    }
+}
+
+#[get("/games/{id}")]
+async fn get_game(data: Data<AppData>, id: Path<String>) -> impl Responder {
+    match find_game(&data.games, &id) {
+        Ok((id, game)) => json_response(&summarize_game(&id, &game)),
+        Err(response) => response,
+    }
+}
+
+#[get("/games/{id}/raw")]
+async fn get_raw_game(data: Data<AppData>, id: Path<String>) -> impl Responder {
+    match find_game(&data.games, &id) {
+        Ok((_, game)) => json_response(&with_names(&game)),
+        Err(response) => response,
+    }
+}
+
+/// The game as it was loaded, with the names of the players and locations next to their IDs in the kill feed and
+/// the starting locations. Unknown IDs get `null` as the name.
+fn with_names(game: &GameStats) -> Value {
+    let player_name = |steam_id: Option<SteamId>| {
+        let player_stat = steam_id.and_then(|steam_id| game.player_stats.get(&steam_id));
+        json!(player_stat.map(|player_stat| &player_stat.player_name))
+    };
+    let location_name = |location: Option<Location>| json!(location.and_then(|location| game.location_name(location)));
+
+    let mut value = serde_json::to_value(game).unwrap_or_default();
+    if let Some(kill_feed) = value["KillFeed"].as_array_mut() {
+        for (kill, value) in game.kill_feed.iter().zip(kill_feed) {
+            let Some(value) = value.as_object_mut() else {
+                continue;
+            };
+            value.insert("killerName".to_owned(), player_name(kill.killer_steam_id));
+            value.insert("victimName".to_owned(), player_name(Some(kill.victim_steam_id)));
+            value.insert("killerLocationName".to_owned(), location_name(kill.killer_location));
+            value.insert("doerLocationName".to_owned(), location_name(kill.doer_location));
+            value.insert("victimLocationName".to_owned(), location_name(kill.victim_location));
+        }
+    }
+    let starting_locations = &game.round_info.starting_locations;
+    value["RoundInfo"]["startingLocationNames"] = json!({
+        "1": location_name(Some(starting_locations.marines)),
+        "2": location_name(Some(starting_locations.aliens)),
+    });
+    value
+}
@@ -1,26 +1,37 @@
 mod data;
+mod games;
+mod players;
+mod store;
+mod watch;
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
 use std::io;
+use std::mem;
 use std::net::{IpAddr, SocketAddr};
-use std::ops::Bound;
 use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use actix_web::web::Json;
 use actix_web::{
     body::EitherBody,
-    error::JsonPayloadError,
+    error::{InternalError, JsonPayloadError, QueryPayloadError},
     get,
-    http::header::ContentType,
-    web::{Data, Query},
-    App, HttpResponse, HttpServer, Responder,
+    http::{header::ContentType, StatusCode},
+    web::{Data, Query, QueryConfig},
+    App, HttpRequest, HttpResponse, HttpServer, Responder,
 };
 use clap::Parser;
-use notify::Watcher;
-use ns2_stat::{summarize_game, GameIterator, GameSummary, NS2Stats};
-use ns2_stat::input_types::GameStats;
+use data::{Loaded, RejectedFile};
+use ns2_stat::filter::GameFilter;
+use ns2_stat::hive_skill::{HiveSkillHistory, HiveSkillSample, HiveSkillSummary};
+use ns2_stat::input_types::SteamId;
+use ns2_stat::load::{self, ErrorChain, LoadOptions, OnError};
+use ns2_stat::prediction::{Roster, WinModel, WinPrediction};
+use ns2_stat::NS2Stats;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
+use store::Store;
 
 fn json_response<T: Serialize>(data: &T) -> HttpResponse<EitherBody<String>> {
     match serde_json::to_string(data) {
@@ -32,10 +43,127 @@ fn json_response<T: Serialize>(data: &T) -> HttpResponse<EitherBody<String>> {
     }
 }
 
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+fn error_response(status: StatusCode, error: String) -> HttpResponse<EitherBody<String>> {
+    let mut response = json_response(&ErrorResponse { error });
+    *response.status_mut() = status;
+    response
+}
+
+fn not_found(error: String) -> HttpResponse<EitherBody<String>> {
+    error_response(StatusCode::NOT_FOUND, error)
+}
+
+/// The response when the games can't be read from the database.
+fn internal_error(error: String) -> HttpResponse<EitherBody<String>> {
+    error_response(StatusCode::INTERNAL_SERVER_ERROR, error)
+}
+
+/// Responds to invalid query parameters with an [`ErrorResponse`] too.
+fn query_error(err: QueryPayloadError, _: &HttpRequest) -> actix_web::Error {
+    let response = error_response(StatusCode::BAD_REQUEST, err.to_string());
+    InternalError::from_response(err, response.map_into_boxed_body()).into()
+}
+
 struct AppData {
-    games: RwLock<BTreeMap<u32, GameStats>>,
+    games: Store,
+    status: RwLock<Status>,
     stats: RwLock<NS2Stats>,
+    model: RwLock<WinModel>,
+    /// The data path, or the database if the games are served from one.
     path: PathBuf,
+    options: LoadOptions,
+    config: Option<PathBuf>,
+}
+
+/// The state of the loaded data, for monitoring.
+#[derive(Serialize)]
+struct Status {
+    /// The Unix time of the last successful load or update.
+    loaded_at: u64,
+    games: usize,
+    /// The files that were skipped on the last successful load.
+    rejected: Vec<RejectedFile>,
+    /// Why the last reload failed, if it did. The data of the last successful load is still used.
+    reload_error: Option<String>,
+}
+
+impl Status {
+    fn new(loaded: &mut Loaded) -> Self {
+        Self::with_games(loaded.games.len(), mem::take(&mut loaded.rejected))
+    }
+
+    fn with_games(games: usize, rejected: Vec<RejectedFile>) -> Self {
+        Self {
+            loaded_at: Self::now(),
+            games,
+            rejected,
+            reload_error: None,
+        }
+    }
+
+    fn now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs())
+    }
+}
+
+/// The number of items in a page without a `limit`.
+const DEFAULT_LIMIT: usize = 50;
+/// The maximum number of items in a page.
+const MAX_LIMIT: usize = 1000;
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+struct PageQuery {
+    /// The number of items to skip.
+    offset: Option<usize>,
+    /// The maximum number of items.
+    limit: Option<usize>,
+}
+
+/// A part of a list.
+#[derive(Serialize)]
+struct Page<T> {
+    /// The number of items in the whole list.
+    total: usize,
+    offset: usize,
+    limit: usize,
+    items: Vec<T>,
+}
+
+impl<T> Page<T> {
+    fn new(items: Vec<T>, query: &PageQuery) -> Self {
+        let offset = query.offset.unwrap_or(0);
+        let limit = query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+        Self {
+            total: items.len(),
+            offset,
+            limit,
+            items: items.into_iter().skip(offset).take(limit).collect(),
+        }
+    }
+
+    fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            total: self.total,
+            offset: self.offset,
+            limit: self.limit,
+            items: self.items.into_iter().map(f).collect(),
+        }
+    }
+
+    /// Replaces the items, e.g. the IDs of the games in the page with their summaries.
+    fn with_items<U>(&self, items: Vec<U>) -> Page<U> {
+        Page {
+            total: self.total,
+            offset: self.offset,
+            limit: self.limit,
+            items,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Deserialize)]
@@ -45,17 +173,52 @@ struct DateQuery {
 }
 
 impl DateQuery {
-    fn to_range_bounds(self) -> (Bound<u32>, Bound<u32>) {
-        (
-            match self.from {
-                Some(bound) => Bound::Included(bound),
-                None => Bound::Unbounded,
-            },
-            match self.to {
-                Some(bound) => Bound::Included(bound),
-                None => Bound::Unbounded,
-            },
-        )
+    /// The genuine games in the date range.
+    fn to_filter(self) -> GameFilter {
+        GameFilter {
+            from: self.from,
+            to: self.to,
+            ..GameFilter::genuine()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+struct HiveSkillQuery {
+    /// The number of games used to compute the trend.
+    window: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct HiveSkillResponse {
+    /// The name in the latest game.
+    name: String,
+    #[serde(flatten)]
+    summary: HiveSkillSummary,
+    samples: Vec<HiveSkillSample>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PredictQuery {
+    /// The marine players, separated by commas.
+    marines: String,
+    /// The alien players, separated by commas.
+    aliens: String,
+    marine_commander: Option<String>,
+    alien_commander: Option<String>,
+    map: Option<String>,
+}
+
+impl PredictQuery {
+    fn into_roster(self) -> Roster {
+        let split = |players: String| players.split(',').filter(|player| !player.is_empty()).map(|player| player.to_owned()).collect();
+        Roster {
+            marines: split(self.marines),
+            aliens: split(self.aliens),
+            marine_commander: self.marine_commander,
+            alien_commander: self.alien_commander,
+            map_name: self.map,
+        }
     }
 }
 
@@ -65,68 +228,128 @@ async fn get_stats(data: Data<AppData>) -> impl Responder {
 }
 
 #[get("/stats/continuous")]
-async fn get_continuous_stats(data: Data<AppData>, query: Query<DateQuery>) -> Json<BTreeMap<u32, NS2Stats>> {
-    let games = data.games.read();
-    let game_stats = games.range(query.to_range_bounds()).map(|(_, game)| game).genuine().collect::<Vec<_>>();
-    let continuous_stats = (0..game_stats.len())
-        .map(|i| (game_stats[i].round_info.round_date, NS2Stats::compute(game_stats[..=i].iter().copied())))
-        .collect::<BTreeMap<_, _>>();
-    Json(continuous_stats)
+async fn get_continuous_stats(data: Data<AppData>, query: Query<DateQuery>) -> impl Responder {
+    let mut stats = NS2Stats::default();
+    let mut continuous_stats = BTreeMap::new();
+    let result = data.games.for_each_game(&query.to_filter(), |id, game| {
+        stats.add_game(game);
+        continuous_stats.insert(id.clone(), stats.clone());
+    });
+    match result {
+        Ok(()) => json_response(&continuous_stats),
+        Err(err) => internal_error(err),
+    }
 }
 
-#[get("/games")]
-async fn get_games(data: Data<AppData>, query: Query<DateQuery>) -> Json<BTreeMap<u32, GameSummary>> {
-    let games = data.games.read();
-    Json(games.range(query.to_range_bounds()).map(|(&date, game)| (date, summarize_game(game))).collect())
+#[get("/stats/hive_skill")]
+async fn get_hive_skill(data: Data<AppData>, query: Query<HiveSkillQuery>) -> impl Responder {
+    let window = query.window.unwrap_or(10);
+    let mut histories = HashMap::new();
+    // the games are in chronological order
+    if let Err(err) = data
+        .games
+        .for_each_game(&GameFilter::genuine(), |_, game| HiveSkillHistory::add_game(&mut histories, game))
+    {
+        return internal_error(err);
+    }
+    json_response(
+        &histories
+            .into_iter()
+            .filter_map(|(steam_id, history)| {
+                let summary = history.summary(window)?;
+                Some((
+                    steam_id,
+                    HiveSkillResponse {
+                        name: history.name,
+                        summary,
+                        samples: history.samples,
+                    },
+                ))
+            })
+            .collect::<HashMap<SteamId, _>>(),
+    )
+}
+
+#[get("/predict")]
+async fn get_prediction(data: Data<AppData>, query: Query<PredictQuery>) -> Json<WinPrediction> {
+    Json(data.model.read().predict(&query.into_inner().into_roster()))
 }
 
-#[get("/games/latest")]
-async fn get_latest_games(data: Data<AppData>) -> Json<GameSummary> {
-    let games = data.games.read();
-    let latest_game = games.last_key_value().unwrap().1;
-    Json(summarize_game(latest_game))
+#[get("/status")]
+async fn get_status(data: Data<AppData>) -> impl Responder {
+    json_response(&*data.status.read())
 }
 
 #[actix_web::main]
 async fn main() -> io::Result<()> {
-    let args = CliArgs::parse();
-    let games = data::load(&args.data_path)?;
+    let mut args = CliArgs::parse();
+    // events of some platforms have canonical paths, which have to match the paths of the loaded files
+    for path in [args.data_path.as_mut(), args.database.as_mut(), args.config.as_mut()].into_iter().flatten() {
+        if let Ok(canonical) = fs::canonicalize(&*path) {
+            *path = canonical;
+        }
+    }
+    let options = LoadOptions {
+        cache: if args.no_cache {
+            None
+        } else {
+            args.cache.clone().or_else(|| args.data_path.as_ref().and_then(load::default_cache_path))
+        },
+        // a broken file, e.g. one the server is still writing, must not keep the other games from loading
+        on_error: OnError::Collect,
+    };
+    let (games, status, loaded) = match &args.database {
+        Some(database) => {
+            let config = data::load_database_config(args.config.as_deref()).map_err(|e| io::Error::other(ErrorChain(&e).to_string()))?;
+            let games = Store::open_database(database, config).map_err(io::Error::other)?;
+            let status = Status::with_games(games.len().map_err(io::Error::other)?, Vec::new());
+            (games, status, None)
+        }
+        None => {
+            let data_path = args.data_path.as_ref().expect("the data path is required without a database");
+            let mut loaded = data::load(data_path, &options, args.config.as_deref()).map_err(io::Error::other)?;
+            let status = Status::new(&mut loaded);
+            let games = Store::Memory(RwLock::new(mem::take(&mut loaded.games)));
+            (games, status, Some(loaded))
+        }
+    };
+    let (stats, model) = games.compute().map_err(io::Error::other)?;
 
     let data = Data::new(AppData {
-        stats: RwLock::new(NS2Stats::compute(games.values().genuine())),
-        games: RwLock::new(games),
-        path: args.data_path,
+        games,
+        status: RwLock::new(status),
+        stats: RwLock::new(stats),
+        model: RwLock::new(model),
+        path: args.database.or(args.data_path).expect("either a data path or a database is given"),
+        options,
+        config: args.config,
     });
 
-    let watcher_data = data.clone();
-    let mut watcher = notify::recommended_watcher(move |res| match res {
-        Ok(_) => {
-            // reload all data
-            println!("reloading data...");
-            let games = match data::load(&watcher_data.path) {
-                Ok(games) => games,
-                Err(err) => {
-                    eprintln!("Error: {:?}", err);
-                    return;
-                }
-            };
-            *watcher_data.stats.write() = NS2Stats::compute(games.values().genuine());
-            *watcher_data.games.write() = games;
-        }
-        Err(e) => eprintln!("notify error: {:?}", e),
-    })
-    .unwrap();
-    watcher.watch(&data.path, notify::RecursiveMode::NonRecursive).unwrap();
+    let _watcher = match loaded {
+        Some(loaded) => watch::watch(data.clone(), loaded.sources, loaded.config),
+        None => watch::watch_database(data.clone()),
+    }
+    .map_err(io::Error::other)?;
 
     let addr = SocketAddr::new(args.address, args.port);
     println!("starting server at {}...", addr);
     HttpServer::new(move || {
         App::new()
             .app_data(data.clone())
+            .app_data(QueryConfig::default().error_handler(query_error))
             .service(get_stats)
             .service(get_continuous_stats)
-            .service(get_games)
-            .service(get_latest_games)
+            .service(get_hive_skill)
+            .service(get_prediction)
+            .service(games::get_games)
+            // before `/games/{id}`, which would match it too
+            .service(games::get_latest_game)
+            .service(games::get_game)
+            .service(games::get_raw_game)
+            .service(get_status)
+            .service(players::get_players)
+            .service(players::get_player)
+            .service(players::get_player_games)
     })
     .bind(addr)?
     .run()
@@ -136,9 +359,24 @@ async fn main() -> io::Result<()> {
 #[derive(Debug, Parser)]
 struct CliArgs {
     /// The path for the game data.
-    data_path: PathBuf,
+    #[clap(required_unless_present = "database")]
+    data_path: Option<PathBuf>,
+    /// Serve the games of this SQLite database instead of a data path, see the `store` command of the CLI. The games
+    /// are read when they are needed instead of being kept in memory, for archives that are too large for that.
+    #[clap(long, conflicts_with_all = ["data_path", "cache", "no_cache"])]
+    database: Option<PathBuf>,
     #[clap(long, default_value = "127.0.0.1")]
     address: IpAddr,
     #[clap(long, short, default_value = "8080")]
     port: u16,
+    /// The file to cache parsed games in, by default in the cache directory of the user.
+    #[clap(long)]
+    cache: Option<PathBuf>,
+    /// Parse all games instead of using the cache.
+    #[clap(long, conflicts_with = "cache")]
+    no_cache: bool,
+    /// The configuration with player merges and exclusions, by default `ns2-stat.toml` in the data path. A database
+    /// has no default configuration.
+    #[clap(long)]
+    config: Option<PathBuf>,
 }
@@ -0,0 +1,126 @@
+//! The endpoints for single players, so clients don't need all stats to show one player.
+
+use actix_web::web::{Data, Path, Query};
+use actix_web::{get, Responder};
+use ns2_stat::filter::GameFilter;
+use ns2_stat::input_types::Team;
+use ns2_stat::{GameSummary, Stat, User, WinningTeam};
+use serde::{Deserialize, Serialize};
+
+use crate::games::summarize_games;
+use crate::{internal_error, json_response, not_found, AppData, Page, PageQuery};
+
+#[derive(Debug, Deserialize)]
+struct SearchQuery {
+    /// Only include players whose name contains this, ignoring case.
+    search: Option<String>,
+}
+
+#[derive(Serialize)]
+struct PlayerEntry<'a> {
+    name: &'a str,
+    games: u32,
+    wins: u32,
+    kd: f32,
+}
+
+#[derive(Serialize)]
+struct PlayerResponse<'a> {
+    name: &'a str,
+    #[serde(flatten)]
+    user: &'a User,
+    kd: Stat<f32>,
+    kda: Stat<f32>,
+    accuracy: Stat<f32>,
+    average_score: Stat<f32>,
+}
+
+/// A game from the perspective of a player, whose stats are in the team of the game summary.
+#[derive(Serialize)]
+struct PlayerGameResponse {
+    team: Team,
+    won: bool,
+    commander: bool,
+    game: GameSummary,
+}
+
+impl PlayerGameResponse {
+    fn new(name: &str, game: GameSummary) -> Self {
+        let team = if game.marines.players.contains_key(name) {
+            Team::Marines
+        } else {
+            Team::Aliens
+        };
+        let team_summary = match team {
+            Team::Marines => &game.marines,
+            Team::Aliens => &game.aliens,
+        };
+        Self {
+            team,
+            won: matches!(
+                (game.winning_team, team),
+                (WinningTeam::Marines, Team::Marines) | (WinningTeam::Aliens, Team::Aliens)
+            ),
+            commander: team_summary.is_commander(name),
+            game,
+        }
+    }
+}
+
+#[get("/players")]
+async fn get_players(data: Data<AppData>, query: Query<SearchQuery>, page: Query<PageQuery>) -> impl Responder {
+    let stats = data.stats.read();
+    let search = query.search.as_deref().unwrap_or_default().to_lowercase();
+    let mut players = stats
+        .users
+        .iter()
+        .filter(|(name, _)| name.to_lowercase().contains(&search))
+        .map(|(name, user)| PlayerEntry {
+            name,
+            games: user.games.total,
+            wins: user.wins.total,
+            kd: user.kd().total,
+        })
+        .collect::<Vec<_>>();
+    players.sort_by(|player1, player2| player2.games.cmp(&player1.games).then_with(|| player1.name.cmp(player2.name)));
+    json_response(&Page::new(players, &page))
+}
+
+#[get("/players/{name}")]
+async fn get_player(data: Data<AppData>, name: Path<String>) -> impl Responder {
+    let stats = data.stats.read();
+    let Some((name, user)) = stats.users.get_key_value(name.as_str()) else {
+        return not_found(format!("no player named `{}`", name));
+    };
+    json_response(&PlayerResponse {
+        name,
+        user,
+        kd: user.kd(),
+        kda: user.kda(),
+        accuracy: user.accuracy(),
+        average_score: user.average_score(),
+    })
+}
+
+#[get("/players/{name}/games")]
+async fn get_player_games(data: Data<AppData>, name: Path<String>, page: Query<PageQuery>) -> impl Responder {
+    // the same games as in the stats
+    let filter = GameFilter {
+        player: Some(name.clone()),
+        ..GameFilter::genuine()
+    };
+    let mut player_games = Vec::new();
+    if let Err(err) = data.games.for_each_game(&filter, |id, _| player_games.push(id.clone())) {
+        return internal_error(err);
+    }
+    if player_games.is_empty() {
+        return not_found(format!("no player named `{}`", name));
+    }
+    player_games.reverse();
+    // only the games in the page are read and summarized
+    let page = Page::new(player_games, &page);
+    match summarize_games(&data.games, &page.items) {
+        Ok(summaries) => json_response(&page.with_items(summaries).map(|game| PlayerGameResponse::new(&name, game))),
+        Err(err) => internal_error(err),
+    }
+}
@@ -0,0 +1,204 @@
+//! Where the games are served from: the data path, whose games are kept in memory, or a SQLite database written by
+//! the CLI's `store` command, whose games are read when a request needs them.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use ns2_stat::config::Config;
+use ns2_stat::db::Database;
+use ns2_stat::filter::GameFilter;
+use ns2_stat::input_types::GameStats;
+use ns2_stat::load::ErrorChain;
+use ns2_stat::prediction::{WinModel, WinModelBuilder};
+use ns2_stat::{GameId, NS2Stats};
+use parking_lot::{Mutex, RwLock};
+
+pub enum Store {
+    /// The games of the data path with the configuration applied, kept up to date by the watcher.
+    Memory(RwLock<BTreeMap<GameId, GameStats>>),
+    /// The stored games, which are read one at a time.
+    Database(Box<StoredGames>),
+}
+
+pub struct StoredGames {
+    database: Mutex<Database>,
+    /// Applied to each game when it is read.
+    config: RwLock<Config>,
+}
+
+impl Store {
+    pub fn open_database(path: &Path, config: Config) -> Result<Self, String> {
+        let database = Database::open(path).map_err(|e| format!("failed to open `{}`: {}", path.display(), ErrorChain(&e)))?;
+        Ok(Store::Database(Box::new(StoredGames {
+            database: Mutex::new(database),
+            config: RwLock::new(config),
+        })))
+    }
+
+    /// The games of the data path, which are only kept in memory without a database.
+    pub fn memory(&self) -> Option<&RwLock<BTreeMap<GameId, GameStats>>> {
+        match self {
+            Store::Memory(games) => Some(games),
+            Store::Database(_) => None,
+        }
+    }
+
+    /// Replaces the configuration that is applied to the stored games. The games in memory are loaded again instead.
+    pub fn set_config(&self, new_config: Config) {
+        if let Store::Database(stored) = self {
+            *stored.config.write() = new_config;
+        }
+    }
+
+    /// Calls `f` with each game that matches the filter, in the order of their IDs.
+    pub fn for_each_game(&self, filter: &GameFilter, mut f: impl FnMut(&GameId, &GameStats)) -> Result<(), String> {
+        match self {
+            Store::Memory(games) => {
+                games.read().iter().filter(|(_, game)| filter.matches(game)).for_each(|(id, game)| f(id, game));
+                Ok(())
+            }
+            Store::Database(stored) => {
+                let config = stored.config.read();
+                // the player and whether the game is genuine are checked after applying the configuration, since it
+                // can rename and exclude players
+                let before_config = GameFilter {
+                    player: None,
+                    genuine: false,
+                    ..filter.clone()
+                };
+                stored
+                    .database
+                    .lock()
+                    .for_each_game(&before_config, |id, mut game| {
+                        if !config.is_excluded(&id) {
+                            config.apply_to_game(&mut game);
+                            if filter.matches(&game) {
+                                f(&id, &game);
+                            }
+                        }
+                    })
+                    .map_err(|e| format!("failed to read the games: {}", ErrorChain(&e)))
+            }
+        }
+    }
+
+    /// Calls `f` with each of the games with the IDs in `ids`, in that order. Unknown IDs are skipped.
+    pub fn for_each_id(&self, ids: &[GameId], mut f: impl FnMut(&GameId, &GameStats)) -> Result<(), String> {
+        match self {
+            Store::Memory(games) => {
+                let games = games.read();
+                for (id, game) in ids.iter().filter_map(|id| games.get_key_value(id)) {
+                    f(id, game);
+                }
+                Ok(())
+            }
+            Store::Database(_) => {
+                for id in ids {
+                    if let Some(game) = self.game(id)? {
+                        f(id, &game);
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// The game with the ID `id`, whether it is genuine or not.
+    pub fn game(&self, id: &GameId) -> Result<Option<GameStats>, String> {
+        match self {
+            Store::Memory(games) => Ok(games.read().get(id).cloned()),
+            Store::Database(stored) => {
+                let config = stored.config.read();
+                if config.is_excluded(id) {
+                    return Ok(None);
+                }
+                let game = stored
+                    .database
+                    .lock()
+                    .game(id)
+                    .map_err(|e| format!("failed to read `{}`: {}", id, ErrorChain(&e)))?;
+                Ok(game.map(|mut game| {
+                    config.apply_to_game(&mut game);
+                    game
+                }))
+            }
+        }
+    }
+
+    /// The number of games, whether they are genuine or not.
+    pub fn len(&self) -> Result<usize, String> {
+        match self {
+            Store::Memory(games) => Ok(games.read().len()),
+            Store::Database(stored) => {
+                let ids = stored
+                    .database
+                    .lock()
+                    .ids()
+                    .map_err(|e| format!("failed to read the games: {}", ErrorChain(&e)))?;
+                let config = stored.config.read();
+                Ok(ids.iter().filter(|id| !config.is_excluded(id)).count())
+            }
+        }
+    }
+
+    /// Computes the stats and fits the model on the genuine games, one game at a time.
+    pub fn compute(&self) -> Result<(NS2Stats, WinModel), String> {
+        let mut stats = NS2Stats::default();
+        let mut model = WinModelBuilder::default();
+        self.for_each_game(&GameFilter::genuine(), |_, game| {
+            stats.add_game(game);
+            model.add_game(game);
+        })?;
+        Ok((stats, model.fit()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ns2_stat::load;
+
+    use super::*;
+
+    #[test]
+    fn database_serves_the_same_games() {
+        let mut games = load::load("../test_data").unwrap().games;
+        let excluded = games.keys().next().unwrap().clone();
+        let config = || {
+            Config::parse(&format!(
+                "[[players]]\nname = \"Konsum\"\naliases = [\"konsumlamm\"]\n\n[exclude]\ngames = [\"{}\"]\n",
+                excluded
+            ))
+            .unwrap()
+        };
+        let mut database = Database::open_in_memory().unwrap();
+        database.insert_games(&games).unwrap();
+        let database = Store::Database(Box::new(StoredGames {
+            database: Mutex::new(database),
+            config: RwLock::new(config()),
+        }));
+        config().apply(&mut games);
+        let memory = Store::Memory(RwLock::new(games));
+
+        let ids = |store: &Store, filter: &GameFilter| {
+            let mut ids = Vec::new();
+            store.for_each_game(filter, |id, _| ids.push(id.clone())).unwrap();
+            ids
+        };
+        let player = GameFilter {
+            player: Some("Konsum".to_owned()),
+            ..GameFilter::genuine()
+        };
+        assert!(!ids(&memory, &player).is_empty());
+        for filter in [GameFilter::default(), GameFilter::genuine(), player] {
+            assert_eq!(ids(&database, &filter), ids(&memory, &filter));
+        }
+        assert_eq!(database.len(), memory.len());
+        assert_eq!(database.game(&excluded), Ok(None));
+        let id = &ids(&memory, &GameFilter::genuine())[0];
+        assert_eq!(database.game(id).unwrap(), memory.game(id).unwrap());
+        let (database_stats, _) = database.compute().unwrap();
+        let (memory_stats, _) = memory.compute().unwrap();
+        assert_eq!(database_stats.total_games, memory_stats.total_games);
+        assert_eq!(database_stats.users["Konsum"].games.total, memory_stats.users["Konsum"].games.total);
+    }
+}
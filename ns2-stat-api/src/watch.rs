@@ -0,0 +1,313 @@
+//! Keeps the loaded games up to date with the files in the data path.
+//!
+//! Writing a file causes several events, so events are collected until there are none for a short time. Then only
+//! the changed files are parsed, and the games and stats are updated. Since fitting the model takes all games, it is
+//! only fitted again once the files stayed unchanged for `REFIT_DELAY`. A change of the configuration reloads all games.
+//!
+//! With a database, the database and the configuration are watched instead, and the stats are computed again when the
+//! CLI stores new games.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use actix_web::web::Data;
+use notify::event::{AccessKind, AccessMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use ns2_stat::config::{self, Config};
+use ns2_stat::input_types::GameStats;
+use ns2_stat::load::{ErrorChain, LoadError, Sources, Update};
+use ns2_stat::prediction::WinModel;
+use ns2_stat::{GameId, GameIterator, NS2Stats};
+use parking_lot::RwLock;
+
+use crate::data::{self, RejectedFile};
+use crate::{AppData, Status};
+
+/// Events closer together than this are handled together.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+/// How long to wait before loading a rejected file again without an event, since it may still be written.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+/// How often a rejected file is loaded again without an event.
+const MAX_RETRIES: u32 = 3;
+/// How long the files have to stay unchanged before the model is fitted again after genuine games changed.
+const REFIT_DELAY: Duration = Duration::from_secs(5);
+
+/// Watches `data.path` with `mode` and the configuration.
+fn watcher(data: &AppData, mode: RecursiveMode) -> notify::Result<(RecommendedWatcher, Receiver<notify::Result<Event>>)> {
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        // the receiver only stops when the watcher is dropped
+        let _ = sender.send(event);
+    })?;
+    watcher.watch(&data.path, mode)?;
+    if let Some(config) = &data.config {
+        watcher.watch(config, RecursiveMode::NonRecursive)?;
+    }
+    Ok((watcher, receiver))
+}
+
+/// Watches the data path and the configuration and updates `data` on changes, until the watcher is dropped.
+/// `sources` and `config` have to be the ones the games in `data` were loaded with.
+pub fn watch(data: Data<AppData>, sources: Sources, config: Config) -> notify::Result<RecommendedWatcher> {
+    let (watcher, receiver) = watcher(&data, RecursiveMode::Recursive)?;
+    let state = State {
+        data,
+        sources,
+        config,
+        retries: HashMap::new(),
+        refit: false,
+    };
+    thread::spawn(move || state.run(receiver));
+    Ok(watcher)
+}
+
+/// Watches the database and the configuration and computes the stats of `data` again on changes, until the watcher is
+/// dropped.
+pub fn watch_database(data: Data<AppData>) -> notify::Result<RecommendedWatcher> {
+    let (watcher, receiver) = watcher(&data, RecursiveMode::NonRecursive)?;
+    thread::spawn(move || {
+        while let Some(paths) = collect_changes(&receiver, None) {
+            if !paths.is_empty() {
+                reload_database(&data);
+            }
+        }
+    });
+    Ok(watcher)
+}
+
+/// Reads the configuration again and computes the stats of the stored games. On errors, the old stats are kept.
+fn reload_database(data: &AppData) {
+    println!("reloading the database...");
+    let result = data::load_database_config(data.config.as_deref())
+        .map_err(|e| ErrorChain(&e).to_string())
+        .and_then(|config| {
+            data.games.set_config(config);
+            Ok((data.games.compute()?, data.games.len()?))
+        });
+    match result {
+        Ok(((stats, model), games)) => {
+            *data.stats.write() = stats;
+            *data.model.write() = model;
+            *data.status.write() = Status::with_games(games, Vec::new());
+        }
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            data.status.write().reload_error = Some(err);
+        }
+    }
+}
+
+/// Whether the event can be a change, unlike opening and reading files, e.g. when they are loaded.
+fn is_change(kind: &EventKind) -> bool {
+    match kind {
+        EventKind::Access(access) => *access == AccessKind::Close(AccessMode::Write),
+        _ => true,
+    }
+}
+
+struct State {
+    data: Data<AppData>,
+    sources: Sources,
+    config: Config,
+    /// The rejected sources that are loaded again after `RETRY_DELAY`, with the number of failed attempts.
+    retries: HashMap<PathBuf, u32>,
+    /// Whether genuine games changed since the model was fitted.
+    refit: bool,
+}
+
+/// Waits for changes, at most for `timeout` if given, and collects the changed paths until there are no events for
+/// `DEBOUNCE`. Returns `None` when the watcher was dropped.
+fn collect_changes(receiver: &Receiver<notify::Result<Event>>, timeout: Option<Duration>) -> Option<BTreeSet<PathBuf>> {
+    let mut paths = BTreeSet::new();
+    let mut next = match timeout {
+        Some(timeout) => receiver.recv_timeout(timeout),
+        None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+    };
+    loop {
+        match next {
+            Ok(Ok(event)) if is_change(&event.kind) => paths.extend(event.paths),
+            Ok(Ok(_)) => {}
+            Ok(Err(err)) => eprintln!("notify error: {:?}", err),
+            Err(RecvTimeoutError::Timeout) => return Some(paths),
+            Err(RecvTimeoutError::Disconnected) => return None,
+        }
+        next = receiver.recv_timeout(DEBOUNCE);
+    }
+}
+
+/// The sources to load again after an update, with the number of failed attempts: the rejected ones, until they
+/// failed `MAX_RETRIES` times in a row.
+fn next_retries(retries: &HashMap<PathBuf, u32>, rejected: &[(PathBuf, Vec<LoadError>)]) -> HashMap<PathBuf, u32> {
+    rejected
+        .iter()
+        .map(|(source, _)| (source.clone(), retries.get(source).map_or(1, |attempts| attempts + 1)))
+        .filter(|&(_, attempts)| attempts <= MAX_RETRIES)
+        .collect()
+}
+
+impl State {
+    /// The games in memory, as a function of `data` so that the other fields can be changed while they are locked.
+    fn games(data: &AppData) -> &RwLock<BTreeMap<GameId, GameStats>> {
+        data.games.memory().expect("only the games of a data path are watched")
+    }
+
+    fn run(mut self, receiver: Receiver<notify::Result<Event>>) {
+        loop {
+            let timeout = [(!self.retries.is_empty()).then_some(RETRY_DELAY), self.refit.then_some(REFIT_DELAY)]
+                .into_iter()
+                .flatten()
+                .min();
+            let Some(mut paths) = collect_changes(&receiver, timeout) else {
+                return;
+            };
+            if paths.is_empty() && self.refit {
+                self.refit = false;
+                let games = Self::games(&self.data).read();
+                *self.data.model.write() = WinModel::fit(games.values().genuine());
+            }
+
+            let config_path = match &self.data.config {
+                Some(config) => config.clone(),
+                None => self.data.path.join(config::DEFAULT_FILE_NAME),
+            };
+            if paths.contains(&config_path) {
+                self.reload();
+            } else {
+                paths.extend(self.retries.keys().cloned());
+                if !paths.is_empty() {
+                    let update = self.sources.update(&paths.into_iter().collect::<Vec<_>>());
+                    self.apply(update);
+                }
+            }
+        }
+    }
+
+    /// Loads all games again, e.g. because the configuration changed. On errors, the old games are kept.
+    fn reload(&mut self) {
+        println!("reloading data...");
+        let mut loaded = match data::load(&self.data.path, &self.data.options, self.data.config.as_deref()) {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                let err = ErrorChain(&err).to_string();
+                eprintln!("Error: {}", err);
+                self.data.status.write().reload_error = Some(err);
+                return;
+            }
+        };
+        *self.data.status.write() = Status::new(&mut loaded);
+        self.sources = loaded.sources;
+        self.config = loaded.config;
+        self.retries.clear();
+        self.refit = false;
+        let games = loaded.games;
+        *self.data.stats.write() = NS2Stats::compute(games.values().genuine());
+        *self.data.model.write() = WinModel::fit(games.values().genuine());
+        *Self::games(&self.data).write() = games;
+    }
+
+    fn apply(&mut self, update: Update) {
+        self.retries = next_retries(&self.retries, &update.rejected);
+        for err in update.rejected.iter().flat_map(|(_, errors)| errors) {
+            eprintln!("Warning: skipping a file: {}", ErrorChain(err));
+        }
+        data::report_skipped(&update.duplicates, &update.conflicts);
+        if !update.updated.is_empty() {
+            println!(
+                "updated {} files: {} games added, {} removed",
+                update.updated.len(),
+                update.added.len(),
+                update.removed.len()
+            );
+        }
+
+        let mut games = Self::games(&self.data).write();
+        let removed: Vec<_> = update.removed.iter().filter_map(|id| games.remove(id)).filter(GameStats::is_genuine).collect();
+        let mut added = Vec::new();
+        for (id, mut game) in update.added {
+            if !self.config.is_excluded(&id) {
+                self.config.apply_to_game(&mut game);
+                if game.is_genuine() {
+                    added.push(id.clone());
+                }
+                games.insert(id, game);
+            }
+        }
+        if !removed.is_empty() || !added.is_empty() {
+            let mut stats = self.data.stats.write();
+            for game in &removed {
+                stats.remove_game(game);
+            }
+            if removed.iter().any(|game| game.round_info.round_date == stats.latest_game) {
+                stats.latest_game = games.values().genuine().map(|game| game.round_info.round_date).max().unwrap_or(0);
+            }
+            for id in &added {
+                stats.add_game(&games[id]);
+            }
+            self.refit = true;
+        }
+
+        let mut status = self.data.status.write();
+        status.games = games.len();
+        status
+            .rejected
+            .retain(|file| !update.updated.iter().any(|source| file.path.starts_with(source)));
+        status
+            .rejected
+            .extend(update.rejected.iter().flat_map(|(_, errors)| errors.iter().map(RejectedFile::from)));
+        status.rejected.sort_by(|file1, file2| file1.path.cmp(&file2.path));
+        status.loaded_at = Status::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use notify::event::{CreateKind, ModifyKind};
+
+    use super::*;
+
+    fn event(kind: EventKind, path: &str) -> notify::Result<Event> {
+        Ok(Event::new(kind).add_path(PathBuf::from(path)))
+    }
+
+    #[test]
+    fn collect_debounced_changes() {
+        let (sender, receiver) = mpsc::channel();
+        sender.send(event(EventKind::Create(CreateKind::File), "a.json")).unwrap();
+        // loading a file opens and reads it
+        sender.send(event(EventKind::Access(AccessKind::Open(AccessMode::Read)), "b.json")).unwrap();
+        sender.send(event(EventKind::Access(AccessKind::Close(AccessMode::Write)), "c.json")).unwrap();
+        let late_sender = sender.clone();
+        let late = thread::spawn(move || {
+            thread::sleep(DEBOUNCE / 2);
+            late_sender.send(event(EventKind::Modify(ModifyKind::Any), "d.json")).unwrap();
+        });
+        let paths = collect_changes(&receiver, None).unwrap();
+        late.join().unwrap();
+        assert_eq!(paths, ["a.json", "c.json", "d.json"].map(PathBuf::from).into());
+
+        // with rejected files, it only waits for the retry delay
+        assert_eq!(collect_changes(&receiver, Some(Duration::from_millis(10))), Some(BTreeSet::new()));
+        drop(sender);
+        assert_eq!(collect_changes(&receiver, None), None);
+    }
+
+    #[test]
+    fn retry_rejected_sources() {
+        let rejected = |names: &[&str]| names.iter().map(|name| (PathBuf::from(name), Vec::new())).collect::<Vec<_>>();
+        let mut retries = HashMap::new();
+        for attempt in 1..=MAX_RETRIES {
+            retries = next_retries(&retries, &rejected(&["a.json", "b.json"]));
+            assert_eq!(retries.get(Path::new("a.json")), Some(&attempt));
+        }
+        // a file that is loaded is not retried anymore
+        retries = next_retries(&retries, &rejected(&["a.json"]));
+        assert!(retries.is_empty());
+        retries = next_retries(&retries, &rejected(&["b.json"]));
+        assert_eq!(retries.get(Path::new("b.json")), Some(&1));
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use ns2_stat::anonymize::Anonymizer;
+use ns2_stat::input_types::GameStats;
+use ns2_stat::GameId;
+
+use crate::helpers;
+use crate::table::Report;
+
+/// The absolute path with symbolic links resolved, also if the end of it doesn't exist yet.
+fn resolve(path: &Path) -> PathBuf {
+    if let Ok(path) = fs::canonicalize(path) {
+        return path;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => resolve(parent).join(name),
+        (Some(_), Some(name)) => resolve(Path::new(".")).join(name),
+        _ => path.to_owned(),
+    }
+}
+
+/// Writes the games with pseudonyms to `output`, as files named by their round date. The original file names are not
+/// used, since they can contain the server IP.
+///
+/// Existing files are never overwritten, a number is added to the name instead. `output` must not be inside
+/// `data_path`, so the originals are never mixed with their pseudonymised copies.
+pub fn anonymize(games: &[&(GameId, GameStats)], data_path: &Path, output: &Path, salt: &str) -> Result<Report, String> {
+    if salt.is_empty() {
+        return Err("the salt must not be empty".to_owned());
+    }
+    if resolve(output).starts_with(resolve(data_path)) {
+        return Err(format!(
+            "the output `{}` must not be inside the data path `{}`",
+            output.display(),
+            data_path.display()
+        ));
+    }
+    fs::create_dir_all(output).map_err(|e| format!("failed to create `{}`\n{}", output.display(), e))?;
+    let anonymizer = Anonymizer::new(salt.as_bytes());
+    // the names that are taken, by earlier games or existing files
+    let mut names = HashSet::new();
+    for (_, game) in games {
+        let round_date = game.round_info.round_date;
+        let data = serde_json::to_vec(&anonymizer.game(game)).map_err(|e| format!("failed to serialize a game\n{}", e))?;
+        let candidates = (0..).map(|i| {
+            if i == 0 {
+                format!("{}.json", round_date)
+            } else {
+                format!("{}-{}.json", round_date, i)
+            }
+        });
+        for name in candidates {
+            if names.contains(&name) {
+                continue;
+            }
+            let path = output.join(&name);
+            match helpers::write_new(&path, &data) {
+                Ok(()) => {
+                    names.insert(name);
+                    break;
+                }
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    names.insert(name);
+                }
+                Err(err) => return Err(format!("failed to write `{}`\n{}", path.display(), err)),
+            }
+        }
+    }
+
+    let mut report = Report::new();
+    report.fields("Summary", [("GAMES", games.len().into()), ("DIRECTORY", output.display().to_string().into())]);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use ns2_stat::testing::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn anonymize_keeps_existing_files() {
+        let dir = TempDir::new("anonymize");
+        let (data_path, output) = (dir.join("data"), dir.join("output"));
+        fs::create_dir_all(&data_path).unwrap();
+        fs::create_dir_all(&output).unwrap();
+        let games = crate::load_data("../test_data", &Default::default()).unwrap();
+        let games = games.iter().take(2).collect::<Vec<_>>();
+        let name = format!("{}.json", games[0].1.round_info.round_date);
+
+        assert!(anonymize(&games, &data_path, &data_path, "salt").is_err());
+        assert!(anonymize(&games, &data_path, &data_path.join("anonymized"), "salt").is_err());
+        fs::write(output.join(&name), "original").unwrap();
+        anonymize(&games, &data_path, &output, "salt").unwrap();
+        assert_eq!(fs::read_to_string(output.join(&name)).unwrap(), "original");
+        assert_eq!(fs::read_dir(&output).unwrap().count(), 3);
+    }
+}
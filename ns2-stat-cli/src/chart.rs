@@ -0,0 +1,239 @@
+use std::env;
+use std::io::{self, IsTerminal};
+
+use crate::table::Value;
+
+/// The maximum height of line charts in rows.
+const MAX_HEIGHT: usize = 10;
+/// Charts never get narrower than this, even if the terminal is.
+const MIN_PLOT_WIDTH: usize = 10;
+
+/// How charts are drawn.
+#[derive(Clone, Copy)]
+pub struct Style {
+    /// The available width in characters.
+    pub width: usize,
+    /// Whether Unicode characters can be used, otherwise charts are plain ASCII.
+    pub unicode: bool,
+}
+
+impl Style {
+    /// Uses the width of the terminal, or `COLUMNS` if the output is not a terminal, and checks the locale for UTF-8.
+    pub fn detect() -> Self {
+        let terminal_width = io::stdout()
+            .is_terminal()
+            .then(|| crossterm::terminal::size().ok())
+            .flatten()
+            .map(|(width, _)| width as usize);
+        let width = terminal_width.or_else(|| env::var("COLUMNS").ok()?.parse().ok()).unwrap_or(80);
+        Self {
+            width,
+            unicode: supports_unicode(),
+        }
+    }
+}
+
+fn supports_unicode() -> bool {
+    if cfg!(windows) {
+        return true;
+    }
+    // the first variable that is set determines the character set, like for the locale itself
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .into_iter()
+        .find_map(|var| env::var(var).ok().filter(|value| !value.is_empty()));
+    locale.is_some_and(|locale| {
+        let locale = locale.to_lowercase();
+        locale.contains("utf-8") || locale.contains("utf8")
+    })
+}
+
+pub enum Chart {
+    /// Named series of `(x, y)` points, sorted by `x`. Each point holds its value until the next one, like the RT
+    /// graph of a [`TeamSummary`](ns2_stat::TeamSummary).
+    Lines { series: Vec<(String, Vec<(f32, f32)>)>, x_unit: String },
+    /// A trend in a single row, from the oldest to the newest value.
+    Sparkline(Vec<f32>),
+    /// Labeled horizontal bars, a bar with the value `max` fills the whole width.
+    Bars { bars: Vec<(String, Value)>, max: f32 },
+}
+
+impl Chart {
+    pub fn render(&self, style: Style) -> String {
+        match self {
+            Chart::Lines { series, x_unit } => render_lines(series, x_unit, style),
+            Chart::Sparkline(values) => render_sparkline(values, style),
+            Chart::Bars { bars, max } => render_bars(bars, *max, style),
+        }
+    }
+}
+
+/// Formats axis labels without decimals if they are not needed.
+fn format_label(n: f32) -> String {
+    if n.fract() == 0.0 {
+        format!("{:.0}", n)
+    } else {
+        format!("{:.1}", n)
+    }
+}
+
+fn render_lines(series: &[(String, Vec<(f32, f32)>)], x_unit: &str, style: Style) -> String {
+    let points = || series.iter().flat_map(|(_, points)| points);
+    let (Some(x_min), Some(x_max)) = (
+        points().map(|&(x, _)| x).min_by(f32::total_cmp),
+        points().map(|&(x, _)| x).max_by(f32::total_cmp),
+    ) else {
+        return "no data\n".to_owned();
+    };
+    let y_max = points().map(|&(_, y)| y).fold(0.0, f32::max);
+    // small counts like RTs get a row per value
+    let height = if points().all(|&(_, y)| y.fract() == 0.0) && y_max < MAX_HEIGHT as f32 {
+        y_max as usize + 1
+    } else {
+        MAX_HEIGHT
+    };
+    let y_labels = [format_label(y_max), "0".to_owned()];
+    let label_width = y_labels.iter().map(|label| label.len()).max().unwrap_or(0);
+    let plot_width = style.width.saturating_sub(label_width + 1).max(MIN_PLOT_WIDTH);
+    let (symbols, overlap, vertical, corner, horizontal) = if style.unicode {
+        (['•', '◦', '▪'], '◆', '│', '└', '─')
+    } else {
+        (['*', 'o', '+'], '#', '|', '+', '-')
+    };
+
+    let mut grid = vec![vec![' '; plot_width]; height];
+    for (i, (_, points)) in series.iter().enumerate() {
+        let symbol = symbols[i % symbols.len()];
+        // the value at `x` is the one of the last point at or before `x`
+        let value = |x: f32| points.iter().take_while(|&&(point_x, _)| point_x <= x).last().map(|&(_, y)| y);
+        let xs = (0..plot_width).map(|column| x_min + (x_max - x_min) * column as f32 / (plot_width - 1) as f32);
+        for (column, y) in xs.enumerate().filter_map(|(column, x)| Some((column, value(x)?))) {
+            let row = if y_max > 0.0 { (y / y_max * (height - 1) as f32).round() as usize } else { 0 };
+            let cell = &mut grid[height - 1 - row.min(height - 1)][column];
+            *cell = if *cell == ' ' || *cell == symbol { symbol } else { overlap };
+        }
+    }
+
+    let mut out = String::new();
+    for (row, cells) in grid.into_iter().enumerate() {
+        let label = match row {
+            0 => &y_labels[0],
+            _ if row == height - 1 => &y_labels[1],
+            _ => "",
+        };
+        let line = format!("{:>width$}{}{}", label, vertical, cells.into_iter().collect::<String>(), width = label_width);
+        out += line.trim_end();
+        out.push('\n');
+    }
+    out += &format!("{:width$}{}{}\n", "", corner, horizontal.to_string().repeat(plot_width), width = label_width);
+    let x_labels = (format_label(x_min), format!("{} {}", format_label(x_max), x_unit));
+    out += &format!(
+        "{:width$} {}{:>rest$}\n",
+        "",
+        x_labels.0,
+        x_labels.1,
+        width = label_width,
+        rest = plot_width.saturating_sub(x_labels.0.len())
+    );
+    let legend = series
+        .iter()
+        .enumerate()
+        .map(|(i, (name, _))| format!("{} {}", symbols[i % symbols.len()], name))
+        .collect::<Vec<_>>();
+    out += &format!("{:width$} {}\n", "", legend.join("  "), width = label_width);
+    out
+}
+
+fn render_sparkline(values: &[f32], style: Style) -> String {
+    let values = values.iter().copied().filter(|value| value.is_finite()).collect::<Vec<_>>();
+    let (Some(min), Some(max)) = (values.iter().copied().reduce(f32::min), values.iter().copied().reduce(f32::max)) else {
+        return "no data\n".to_owned();
+    };
+    let levels = if style.unicode { "▁▂▃▄▅▆▇█" } else { "_.-:=+*#" }.chars().collect::<Vec<_>>();
+    let suffix = format!("  min {:.2}, max {:.2}", min, max);
+    let width = style.width.saturating_sub(suffix.len()).max(MIN_PLOT_WIDTH);
+
+    // if there are more values than fit, each character shows the average of a bucket
+    let buckets = values.len().min(width);
+    let line = (0..buckets)
+        .map(|bucket| {
+            let bucket = &values[bucket * values.len() / buckets..(bucket + 1) * values.len() / buckets];
+            let value = bucket.iter().sum::<f32>() / bucket.len() as f32;
+            let level = if max > min {
+                ((value - min) / (max - min) * (levels.len() - 1) as f32).round() as usize
+            } else {
+                0
+            };
+            levels[level]
+        })
+        .collect::<String>();
+    format!("{}{}\n", line, suffix)
+}
+
+fn render_bars(bars: &[(String, Value)], max: f32, style: Style) -> String {
+    let values = bars.iter().map(|(_, value)| value.to_string()).collect::<Vec<_>>();
+    let label_width = bars.iter().map(|(label, _)| label.chars().count()).max().unwrap_or(0);
+    let value_width = values.iter().map(|value| value.chars().count()).max().unwrap_or(0);
+    let bar_width = style.width.saturating_sub(label_width + value_width + 2).max(MIN_PLOT_WIDTH);
+
+    let mut out = String::new();
+    for ((label, value), text) in bars.iter().zip(values) {
+        let fraction = value.as_f32().filter(|n| n.is_finite() && max > 0.0).map_or(0.0, |n| (n / max).clamp(0.0, 1.0));
+        let length = fraction * bar_width as f32;
+        let bar = if style.unicode {
+            // the partial blocks show eighths of a character
+            let partial = ["", "▏", "▎", "▍", "▌", "▋", "▊", "▉", "█"][(length.fract() * 8.0).round() as usize];
+            "█".repeat(length as usize) + partial
+        } else {
+            "#".repeat(length.round() as usize)
+        };
+        out += &format!(
+            "{:<lw$} {:<bw$} {:>vw$}\n",
+            label,
+            bar,
+            text,
+            lw = label_width,
+            bw = bar_width,
+            vw = value_width
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_charts() {
+        let style = Style { width: 30, unicode: false };
+        let lines = Chart::Lines {
+            series: vec![
+                ("Marines".to_owned(), vec![(0.0, 1.0), (5.0, 2.0), (10.0, 2.0)]),
+                ("Aliens".to_owned(), vec![(0.0, 1.0), (3.0, 0.0), (10.0, 0.0)]),
+            ],
+            x_unit: "min".to_owned(),
+        };
+        let rendered = lines.render(style);
+        let rows = rendered.lines().collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            [
+                "2|              **************",
+                " |#########*****",
+                "0|         ooooooooooooooooooo",
+                " +----------------------------",
+                "  0                     10 min",
+                "  * Marines  o Aliens",
+            ]
+        );
+
+        let sparkline = Chart::Sparkline(vec![0.0, 1.0, 2.0, f32::INFINITY, 3.0]);
+        assert_eq!(sparkline.render(Style { width: 80, unicode: true }), "▁▃▆█  min 0.00, max 3.00\n");
+
+        let bars = Chart::Bars {
+            bars: vec![("a".to_owned(), Value::percent(50.0)), ("bb".to_owned(), Value::percent(100.0))],
+            max: 100.0,
+        };
+        assert_eq!(bars.render(style), "a  ##########           50.00%\nbb ################### 100.00%\n");
+    }
+}
@@ -0,0 +1,29 @@
+use std::path::Path;
+
+use ns2_stat::db::Database;
+use ns2_stat::filter::GameFilter;
+use ns2_stat::input_types::GameStats;
+use ns2_stat::load::ErrorChain;
+use ns2_stat::GameId;
+
+use crate::table::Report;
+
+fn open(path: &Path) -> Result<Database, String> {
+    Database::open(path).map_err(|e| format!("failed to open `{}`\n{:#}", path.display(), ErrorChain(&e)))
+}
+
+/// Stores the games in the SQLite database at `path`, skipping the ones that are already stored.
+pub fn store(path: &Path, games: &[(GameId, GameStats)]) -> Result<Report, String> {
+    let mut database = open(path)?;
+    let added = database
+        .insert_games(games.iter().map(|(id, game)| (id, game)))
+        .map_err(|e| format!("failed to store the games\n{:#}", ErrorChain(&e)))?;
+    let mut report = Report::new();
+    report.fields("Summary", [("ADDED", added.into()), ("SKIPPED", (games.len() - added).into())]);
+    Ok(report)
+}
+
+/// Loads the games that match the filter from the SQLite database at `path`, sorted by their ID.
+pub fn load(path: &Path, filter: &GameFilter) -> Result<Vec<(GameId, GameStats)>, String> {
+    open(path)?.games(filter).map_err(|e| format!("failed to read the games\n{:#}", ErrorChain(&e)))
+}
@@ -0,0 +1,19 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use ns2_stat::input_types::GameStats;
+use ns2_stat::{summarize_game, GameId, GameSummary};
+
+/// Write the summaries of the games as a JSON array, to `output` or to stdout.
+pub fn export(games: &[&(GameId, GameStats)], output: Option<&Path>) -> Result<(), String> {
+    let summaries = games.iter().map(|(id, game)| summarize_game(id, game)).collect::<Vec<GameSummary>>();
+    let result = match output {
+        Some(path) => {
+            let file = File::create(path).map_err(|e| format!("failed to create `{}`\n{}", path.display(), e))?;
+            serde_json::to_writer_pretty(file, &summaries)
+        }
+        None => serde_json::to_writer_pretty(io::stdout().lock(), &summaries).map(|_| println!()),
+    };
+    result.map_err(|e| format!("failed to write the games\n{}", e))
+}
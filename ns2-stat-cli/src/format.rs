@@ -0,0 +1,267 @@
+use clap::ValueEnum;
+use serde_json::{json, Map};
+
+use crate::chart::{Chart, Style};
+use crate::table::{self, Alignment, Report, Section, Value};
+
+/// Charts in Markdown and HTML are not shown in a terminal, so they get a fixed width.
+const DOCUMENT_CHART_STYLE: Style = Style { width: 80, unicode: true };
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum Format {
+    #[default]
+    Table,
+    // a JSON object with a key per section
+    Json,
+    // comma-separated values, sections are separated by an empty line
+    Csv,
+    Markdown,
+    Html,
+}
+
+impl Format {
+    pub fn render(self, report: &Report) -> String {
+        match self {
+            Format::Table => table::render_text(report, Style::detect()),
+            Format::Json => render_json(report),
+            Format::Csv => render_csv(report),
+            Format::Markdown => render_markdown(report),
+            Format::Html => render_html(report),
+        }
+    }
+}
+
+/// Converts a title to a key for JSON, e.g. `AVG SCORE` to `avg_score`.
+fn key(title: &str) -> String {
+    let words = title.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty());
+    words.map(|word| word.to_lowercase()).collect::<Vec<_>>().join("_")
+}
+
+fn json_number(n: f32) -> serde_json::Value {
+    // going through the string representation avoids printing `0.1` as `0.10000000149011612`
+    if n.is_finite() {
+        json!(n.to_string().parse::<f64>().unwrap())
+    } else {
+        serde_json::Value::Null
+    }
+}
+
+fn json_value(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Text(text) => json!(text),
+        Value::Integer(n) => json!(n),
+        Value::Number(n, _) | Value::Percent(n) => json_number(*n),
+        Value::Missing => serde_json::Value::Null,
+    }
+}
+
+/// The data of a chart: line series as `[x, y]` pairs by series, sparklines as arrays and bars by label.
+fn json_chart(chart: &Chart) -> serde_json::Value {
+    match chart {
+        Chart::Lines { series, .. } => series
+            .iter()
+            .map(|(name, points)| (key(name), points.iter().map(|&(x, y)| json!([json_number(x), json_number(y)])).collect()))
+            .collect::<Map<_, _>>()
+            .into(),
+        Chart::Sparkline(values) => values.iter().copied().map(json_number).collect(),
+        Chart::Bars { bars, .. } => bars
+            .iter()
+            .map(|(label, value)| (label.clone(), json_value(value)))
+            .collect::<Map<_, _>>()
+            .into(),
+    }
+}
+
+fn render_json(report: &Report) -> String {
+    let mut sections = Map::new();
+    for (title, section) in &report.sections {
+        let value = match section {
+            Section::Table { columns, rows } => rows
+                .iter()
+                .map(|row| {
+                    columns
+                        .iter()
+                        .zip(row)
+                        .map(|((column, _), value)| (key(column), json_value(value)))
+                        .collect::<Map<_, _>>()
+                })
+                .collect(),
+            Section::Fields(fields) => fields.iter().map(|(name, value)| (key(name), json_value(value))).collect::<Map<_, _>>().into(),
+            Section::Chart(chart) => json_chart(chart),
+        };
+        sections.insert(key(title), value);
+    }
+    let mut out = serde_json::to_string_pretty(&sections).unwrap();
+    out.push('\n');
+    out
+}
+
+/// The raw value for formats that don't need alignment. Missing values are empty.
+fn plain(value: &Value) -> String {
+    match value {
+        Value::Missing => String::new(),
+        Value::Percent(n) => format!("{:.2}", n),
+        value => value.to_string(),
+    }
+}
+
+pub fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+fn render_csv(report: &Report) -> String {
+    let mut out = String::new();
+    let mut line = |fields: Vec<String>| {
+        out += &fields.iter().map(|field| csv_field(field)).collect::<Vec<_>>().join(",");
+        out.push('\n');
+    };
+    // charts have no tabular representation
+    let sections = report.sections.iter().filter(|(_, section)| !matches!(section, Section::Chart(_)));
+    for (i, (_, section)) in sections.enumerate() {
+        if i > 0 {
+            line(Vec::new());
+        }
+        match section {
+            Section::Table { columns, rows } => {
+                line(columns.iter().map(|(column, _)| column.clone()).collect());
+                for row in rows {
+                    line(row.iter().map(plain).collect());
+                }
+            }
+            Section::Fields(fields) => {
+                line(fields.iter().map(|(name, _)| name.clone()).collect());
+                line(fields.iter().map(|(_, value)| plain(value)).collect());
+            }
+            Section::Chart(_) => unreachable!(),
+        }
+    }
+    out
+}
+
+fn markdown_cell(cell: &str) -> String {
+    cell.replace('|', "\\|")
+}
+
+fn render_markdown(report: &Report) -> String {
+    let mut out = String::new();
+    for (i, (title, section)) in report.sections.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        out += &format!("### {}\n\n", title);
+        match section {
+            Section::Table { columns, rows } => {
+                let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+                out += &line(columns.iter().map(|(column, _)| markdown_cell(column)).collect());
+                out += &line(
+                    columns
+                        .iter()
+                        .map(|(_, alignment)| match alignment {
+                            Alignment::Left => ":--",
+                            Alignment::Center => ":-:",
+                            Alignment::Right => "--:",
+                        })
+                        .map(String::from)
+                        .collect(),
+                );
+                for row in rows {
+                    out += &line(row.iter().map(|value| markdown_cell(&value.to_string())).collect());
+                }
+            }
+            Section::Fields(fields) => {
+                for (name, value) in fields {
+                    out += &format!("- **{}**: {}\n", markdown_cell(name), markdown_cell(&value.to_string()));
+                }
+            }
+            Section::Chart(chart) => out += &format!("```\n{}```\n", chart.render(DOCUMENT_CHART_STYLE)),
+        }
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn render_html(report: &Report) -> String {
+    let mut out = String::new();
+    for (title, section) in &report.sections {
+        out += &format!("<h3>{}</h3>\n", escape_html(title));
+        match section {
+            Section::Table { columns, rows } => {
+                let style = |alignment: &Alignment| match alignment {
+                    Alignment::Left => "left",
+                    Alignment::Center => "center",
+                    Alignment::Right => "right",
+                };
+                out += "<table>\n<thead>\n<tr>";
+                for (column, alignment) in columns {
+                    out += &format!("<th style=\"text-align: {}\">{}</th>", style(alignment), escape_html(column));
+                }
+                out += "</tr>\n</thead>\n<tbody>\n";
+                for row in rows {
+                    out += "<tr>";
+                    for (value, (_, alignment)) in row.iter().zip(columns) {
+                        out += &format!("<td style=\"text-align: {}\">{}</td>", style(alignment), escape_html(&value.to_string()));
+                    }
+                    out += "</tr>\n";
+                }
+                out += "</tbody>\n</table>\n";
+            }
+            Section::Fields(fields) => {
+                out += "<dl>\n";
+                for (name, value) in fields {
+                    out += &format!("<dt>{}</dt><dd>{}</dd>\n", escape_html(name), escape_html(&value.to_string()));
+                }
+                out += "</dl>\n";
+            }
+            Section::Chart(chart) => out += &format!("<pre>\n{}</pre>\n", escape_html(&chart.render(DOCUMENT_CHART_STYLE))),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::row;
+
+    #[test]
+    fn formats_carry_the_same_columns() {
+        let mut report = Report::new();
+        report.fields("Summary", [("TOTAL GAMES", Value::from(2u32))]);
+        report.table(
+            "Leaderboard",
+            ["NAME", "AVG SCORE", "WR"],
+            [Alignment::Left, Alignment::Right, Alignment::Right],
+            [row!["a, b", 0.5f32, Value::percent(50.0)], row!["c", None::<f32>, Value::percent(0.0)]],
+        );
+        assert_eq!(
+            render_json(&report),
+            r#"{
+  "summary": {
+    "total_games": 2
+  },
+  "leaderboard": [
+    {
+      "name": "a, b",
+      "avg_score": 0.5,
+      "wr": 50.0
+    },
+    {
+      "name": "c",
+      "avg_score": null,
+      "wr": 0.0
+    }
+  ]
+}
+"#
+        );
+        assert_eq!(render_csv(&report), "TOTAL GAMES\n2\n\nNAME,AVG SCORE,WR\n\"a, b\",0.50,50.00\nc,,0.00\n");
+        assert!(render_markdown(&report).contains("| NAME | AVG SCORE | WR |\n| :-- | --: | --: |\n| a, b | 0.50 | 50.00% |\n"));
+    }
+}
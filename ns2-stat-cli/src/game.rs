@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+
+use ns2_stat::input_types::{GameStats, SteamId, Team};
+use ns2_stat::{summarize_game, GameId, TeamSummary};
+
+use crate::chart::Chart;
+use crate::helpers;
+use crate::row;
+use crate::table::{Alignment, Report, Value};
+
+/// Kills by the same killer with the same weapon that are at most this many seconds apart are shown in one row.
+const KILL_STREAK_GAP: f32 = 10.0;
+
+/// Selects a game by `latest`, its game ID, its index (0 is the oldest game) or its round date.
+/// The games have to be sorted chronologically.
+pub fn select_game<'a>(games: &[&'a (GameId, GameStats)], selector: &str) -> Result<&'a (GameId, GameStats), String> {
+    if selector == "latest" {
+        return games.last().copied().ok_or_else(|| "there are no games".to_owned());
+    }
+    if let Ok(id) = selector.parse::<GameId>() {
+        return games
+            .iter()
+            .copied()
+            .find(|(other, _)| *other == id)
+            .ok_or_else(|| format!("no game with ID `{}`", id));
+    }
+    let number = selector
+        .parse::<u32>()
+        .map_err(|_| format!("invalid game `{}`, expected `latest`, a game ID, an index or a round date", selector))?;
+    if let Some(&game) = games.get(number as usize) {
+        return Ok(game);
+    }
+    let mut matching = games.iter().copied().filter(|(id, _)| id.round_date == number);
+    match (matching.next(), matching.next()) {
+        (Some(game), None) => Ok(game),
+        (Some(_), Some(_)) => Err(format!("there are multiple games with the round date {}, use the game ID instead", number)),
+        (None, _) => Err(format!("no game with the index or round date {}", number)),
+    }
+}
+
+fn team_table(report: &mut Report, title: &str, team: &TeamSummary) {
+    let mut players = team.players.iter().collect::<Vec<_>>();
+    players.sort_by_key(|(_, player)| std::cmp::Reverse(player.score));
+    report.table(
+        title,
+        ["NAME", "K", "D", "A", "SCORE", "ACCURACY"],
+        [
+            Alignment::Left,
+            Alignment::Right,
+            Alignment::Right,
+            Alignment::Right,
+            Alignment::Right,
+            Alignment::Right,
+        ],
+        players.into_iter().map(|(name, player)| {
+            let name = if team.is_commander(name) { format!("[{}]", name) } else { name.clone() };
+            let attacks = player.hits + player.misses;
+            let accuracy = (attacks > 0).then(|| Value::percent(player.hits as f32 * 100.0 / attacks as f32));
+            row![name, player.kills, player.deaths, player.assists, player.score, accuracy]
+        }),
+    );
+}
+
+/// Consecutive kills by the same killer with the same weapon.
+struct KillRow<'a> {
+    time: f32,
+    last_time: f32,
+    team: Team,
+    /// `None` for kills by structures like whips and sentries.
+    killer: Option<&'a str>,
+    weapon: &'a str,
+    victims: Vec<&'a str>,
+    /// The distinct locations of the victims.
+    locations: Vec<&'a str>,
+}
+
+fn kill_rows(game: &GameStats) -> Vec<KillRow<'_>> {
+    let names = game
+        .player_stats
+        .iter()
+        .map(|(&steam_id, player_stat)| (steam_id, player_stat.player_name.as_str()))
+        .collect::<HashMap<_, _>>();
+    let mut kills = game.kill_feed.iter().collect::<Vec<_>>();
+    kills.sort_by(|kill1, kill2| f32::total_cmp(&kill1.game_time, &kill2.game_time));
+
+    // bots have the Steam ID 0 and no player stats
+    let name = |steam_id: SteamId| names.get(&steam_id).copied().unwrap_or(if steam_id == 0 { "bot" } else { "unknown" });
+    let mut rows = Vec::<KillRow>::new();
+    for kill in kills {
+        let killer = kill.killer_steam_id.map(name);
+        let victim = name(kill.victim_steam_id);
+        let location = kill.victim_location.and_then(|location| game.location_name(location));
+        match rows.last_mut() {
+            Some(row)
+                if row.killer == killer
+                    && row.team == kill.killer_team
+                    && row.weapon == kill.killer_weapon
+                    && kill.game_time - row.last_time <= KILL_STREAK_GAP =>
+            {
+                row.last_time = kill.game_time;
+                row.victims.push(victim);
+                if let Some(location) = location.filter(|location| !row.locations.contains(location)) {
+                    row.locations.push(location);
+                }
+            }
+            _ => rows.push(KillRow {
+                time: kill.game_time,
+                last_time: kill.game_time,
+                team: kill.killer_team,
+                killer,
+                weapon: &kill.killer_weapon,
+                victims: vec![victim],
+                locations: location.into_iter().collect(),
+            }),
+        }
+    }
+    rows
+}
+
+/// One game.
+pub fn game((id, game): &(GameId, GameStats)) -> Report {
+    let summary = summarize_game(id, game);
+    let mut report = Report::new();
+    report.fields(
+        "Game",
+        [
+            ("GAME", summary.id.to_string().into()),
+            ("DATE", helpers::format_date(summary.round_date).into()),
+            ("MAP", summary.map_name.into()),
+            ("LENGTH", helpers::format_duration(summary.round_length).into()),
+            ("WINNER", format!("{:?}", summary.winning_team).into()),
+        ],
+    );
+    team_table(&mut report, "Marines", &summary.marines);
+    team_table(&mut report, "Aliens", &summary.aliens);
+    let rt_points = |team: &TeamSummary| team.rt_graph.iter().map(|&(time, rts)| (time / 60.0, rts as f32)).collect();
+    report.chart(
+        "RTs",
+        Chart::Lines {
+            series: vec![
+                ("Marines".to_owned(), rt_points(&summary.marines)),
+                ("Aliens".to_owned(), rt_points(&summary.aliens)),
+            ],
+            x_unit: "min".to_owned(),
+        },
+    );
+
+    let mut research = game.research.iter().collect::<Vec<_>>();
+    research.sort_by(|research1, research2| f32::total_cmp(&research1.game_time, &research2.game_time));
+    report.table(
+        "Tech",
+        ["TIME", "TEAM", "RESEARCH"],
+        [Alignment::Left, Alignment::Left, Alignment::Left],
+        research.into_iter().map(|research| {
+            row![
+                helpers::format_duration(research.game_time),
+                format!("{:?}", research.team),
+                &research.research_id
+            ]
+        }),
+    );
+
+    report.table(
+        "Kills",
+        ["TIME", "TEAM", "KILLER", "WEAPON", "VICTIMS", "LOCATION"],
+        [
+            Alignment::Left,
+            Alignment::Left,
+            Alignment::Left,
+            Alignment::Left,
+            Alignment::Left,
+            Alignment::Left,
+        ],
+        kill_rows(game).into_iter().map(|row| {
+            let locations = (!row.locations.is_empty()).then(|| row.locations.join(", "));
+            row![
+                helpers::format_duration(row.time),
+                format!("{:?}", row.team),
+                row.killer,
+                row.weapon,
+                row.victims.join(", "),
+                locations
+            ]
+        }),
+    );
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kill_rows_cover_the_kill_feed() {
+        for (_, game) in crate::load_data("../test_data", &Default::default()).unwrap() {
+            let rows = kill_rows(&game);
+            assert_eq!(rows.iter().map(|row| row.victims.len()).sum::<usize>(), game.kill_feed.len());
+            assert!(rows.windows(2).all(|rows| rows[0].time <= rows[1].time));
+        }
+    }
+}
@@ -1,5 +1,8 @@
 use std::cell::RefCell;
 use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
 
 pub struct FormatWith<'a, I, F> {
     iter: RefCell<Option<(I, F)>>,
@@ -34,3 +37,30 @@ where
         Ok(())
     }
 }
+
+/// Formats a Unix time as `YYYY-MM-DD HH:MM` in UTC.
+pub fn format_date(unix_time: u32) -> String {
+    // see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = unix_time / 86400 + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = era * 400 + year_of_era + (month <= 2) as u32;
+    let seconds = unix_time % 86400;
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, seconds / 3600, seconds / 60 % 60)
+}
+
+/// Formats a duration in seconds as `MM:SS`.
+pub fn format_duration(seconds: f32) -> String {
+    let seconds = seconds as u32;
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Writes a new file, and fails if the file exists.
+pub fn write_new(path: &Path, data: &[u8]) -> io::Result<()> {
+    fs::OpenOptions::new().write(true).create_new(true).open(path)?.write_all(data)
+}
@@ -0,0 +1,215 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use ns2_stat::input_types::GameStats;
+use ns2_stat::load::{self, GameFile};
+use ns2_stat::GameId;
+use rayon::prelude::*;
+
+use crate::table::{Alignment, Report};
+use crate::validate;
+use crate::{helpers, row};
+
+/// The files in the archive.
+#[derive(Default)]
+struct Archive {
+    files: Vec<GameFile>,
+    /// The indices of the files by content hash. There can be multiple files with the same hash if they differ.
+    contents: HashMap<u64, Vec<usize>>,
+    /// The games in the archive, to find files of the same round like [`load`](ns2_stat::load::load) does.
+    ids: BTreeSet<GameId>,
+    /// The indices of the files by game.
+    games: HashMap<GameId, usize>,
+    /// All paths, including the ones of files that will be added.
+    paths: HashSet<PathBuf>,
+}
+
+impl Archive {
+    fn new(files: Vec<GameFile>) -> Self {
+        let rounds = files
+            .par_iter()
+            // invalid files in the archive are reported by `validate`, they can't be the same round as anything
+            .map(|file| load::parse(file).ok().map(|game| GameId::new(&game, &file.path)))
+            .collect::<Vec<_>>();
+        let mut archive = Self::default();
+        for (file, round) in files.into_iter().zip(rounds) {
+            archive.add(file, round);
+        }
+        archive
+    }
+
+    fn add(&mut self, file: GameFile, id: Option<GameId>) {
+        let index = self.files.len();
+        self.contents.entry(load::stable_hash(&file.data)).or_default().push(index);
+        if let Some(id) = id {
+            self.ids.insert(id.clone());
+            self.games.insert(id, index);
+        }
+        self.paths.insert(file.path.clone());
+        self.files.push(file);
+    }
+
+    /// The file with exactly this content.
+    fn identical(&self, data: &[u8]) -> Option<&GameFile> {
+        let candidates = self.contents.get(&load::stable_hash(data))?;
+        // the hash alone could collide
+        candidates.iter().map(|&index| &self.files[index]).find(|file| file.data == data)
+    }
+
+    fn same_round(&self, id: &GameId) -> Option<&GameFile> {
+        load::same_round(&self.ids, id).map(|other| &self.files[self.games[other]])
+    }
+
+    /// The path for a new game in the archive at `archive_path`: the round date, like the files written by the server,
+    /// and the server if another round ended in the same second, with a number if that path is taken as well.
+    fn new_path(&self, archive_path: &Path, game: &GameStats) -> PathBuf {
+        let round_date = game.round_info.round_date.to_string();
+        // IPv6 addresses contain `:`, which file names can't contain on Windows
+        let ip = game.server_info.ip.replace(|c: char| !c.is_ascii_alphanumeric() && c != '.', "_");
+        let server = format!("{}-{}-{}", round_date, ip, game.server_info.port);
+        let numbered = (2u32..).map(|n| format!("{}-{}", server, n));
+        [round_date, server.clone()]
+            .into_iter()
+            .chain(numbered)
+            .map(|name| archive_path.join(format!("{}.json", name)))
+            .find(|path| !self.paths.contains(path))
+            .expect("the numbered paths are endless")
+    }
+}
+
+enum Outcome {
+    Added(String),
+    Skipped(String),
+    Rejected(String),
+}
+
+fn import_file(archive_path: &Path, archive: &mut Archive, file: GameFile, dry_run: bool) -> Outcome {
+    let game = match load::parse(&file) {
+        Ok(game) => game,
+        Err(err) => return Outcome::Rejected(validate::problem(err)),
+    };
+    if let Some(other) = archive.identical(&file.data) {
+        return Outcome::Skipped(format!("identical to `{}`", other.path.display()));
+    }
+    if let Some(other) = archive.same_round(&GameId::new(&game, &file.path)) {
+        return Outcome::Skipped(format!("same round as `{}`", other.path.display()));
+    }
+
+    let mut path = archive.new_path(archive_path, &game);
+    if !dry_run {
+        // files that are not in the archive, because they couldn't be read, are never overwritten
+        loop {
+            match helpers::write_new(&path, &file.data) {
+                Ok(()) => break,
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    archive.paths.insert(path);
+                    path = archive.new_path(archive_path, &game);
+                }
+                Err(err) => return Outcome::Rejected(format!("failed to write `{}`: {}", path.display(), err)),
+            }
+        }
+    }
+    let detail = format!("as `{}`", path.display());
+    let id = GameId::new(&game, &path);
+    archive.add(GameFile { path, data: file.data }, Some(id));
+    Outcome::Added(detail)
+}
+
+/// Copies the valid game files from `sources` into the archive at `archive_path`, unless they are already there.
+/// The archive can contain compressed files and archives, but new games are always written as plain JSON files.
+/// With `dry_run`, only reports what would happen.
+pub fn import(archive_path: &Path, archive_files: Vec<GameFile>, sources: Vec<GameFile>, dry_run: bool) -> Result<Report, String> {
+    if !dry_run {
+        fs::create_dir_all(archive_path).map_err(|e| format!("failed to create `{}`\n{}", archive_path.display(), e))?;
+    }
+    let mut archive = Archive::new(archive_files);
+
+    let mut rows = Vec::new();
+    let (mut added, mut skipped, mut rejected) = (0u32, 0u32, 0u32);
+    for file in sources {
+        let path = file.path.display().to_string();
+        let (result, detail) = match import_file(archive_path, &mut archive, file, dry_run) {
+            Outcome::Added(detail) => {
+                added += 1;
+                ("added", detail)
+            }
+            Outcome::Skipped(detail) => {
+                skipped += 1;
+                ("skipped", detail)
+            }
+            Outcome::Rejected(detail) => {
+                rejected += 1;
+                ("rejected", detail)
+            }
+        };
+        rows.push(row![path, result, detail]);
+    }
+
+    let mut report = Report::new();
+    report.table("Files", ["FILE", "RESULT", "DETAIL"], [Alignment::Left, Alignment::Left, Alignment::Left], rows);
+    report.fields("Summary", [("ADDED", added.into()), ("SKIPPED", skipped.into()), ("REJECTED", rejected.into())]);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use ns2_stat::testing::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn import_skips_duplicates() {
+        let dir = TempDir::new("import");
+        let (archive_path, source) = (dir.join("archive"), dir.join("source"));
+        fs::create_dir_all(&source).unwrap();
+        let game = fs::read_to_string("../test_data/1629228969.json").unwrap();
+        fs::write(source.join("a.json"), &game).unwrap();
+        fs::write(source.join("b.json"), &game).unwrap();
+        // the same round, but formatted differently
+        fs::write(source.join("c.json"), format!(" {}", game)).unwrap();
+        fs::write(source.join("d.json"), "{}").unwrap();
+        fs::create_dir_all(&archive_path).unwrap();
+
+        let file = |name: &str| GameFile {
+            path: source.join(name),
+            data: fs::read(source.join(name)).unwrap(),
+        };
+        let mut archive = Archive::default();
+        let outcomes = ["a.json", "b.json", "c.json", "d.json"].map(|name| import_file(&archive_path, &mut archive, file(name), false));
+        assert!(matches!(
+            outcomes,
+            [Outcome::Added(_), Outcome::Skipped(_), Outcome::Skipped(_), Outcome::Rejected(_)]
+        ));
+        assert_eq!(fs::read_to_string(archive_path.join("1629228969.json")).unwrap(), game);
+
+        // importing again doesn't add anything
+        let mut archive = Archive::new(ns2_stat::load::read_files(&archive_path).unwrap());
+        assert!(matches!(import_file(&archive_path, &mut archive, file("c.json"), false), Outcome::Skipped(_)));
+    }
+
+    #[test]
+    fn import_never_overwrites() {
+        let dir = TempDir::new("import-paths");
+        let data = fs::read_to_string("../test_data/1629228969.json").unwrap();
+        let mut game = serde_json::from_str::<GameStats>(&data).unwrap();
+        let mut archive = Archive::default();
+        for name in ["1629228969.json", "1629228969-77.179.12.31-27015.json"] {
+            archive.paths.insert(dir.join(name));
+        }
+        assert_eq!(archive.new_path(&dir, &game), dir.join("1629228969-77.179.12.31-27015-2.json"));
+        game.server_info.ip = "::1".to_owned();
+        assert_eq!(archive.new_path(&dir, &game), dir.join("1629228969-__1-27015.json"));
+
+        // a file that is not in the archive
+        fs::write(dir.join("1629228969.json"), "not a game").unwrap();
+        let file = GameFile {
+            path: PathBuf::from("a.json"),
+            data: data.into_bytes(),
+        };
+        assert!(matches!(import_file(&dir, &mut Archive::default(), file, false), Outcome::Added(_)));
+        assert_eq!(fs::read_to_string(dir.join("1629228969.json")).unwrap(), "not a game");
+        assert!(dir.join("1629228969-77.179.12.31-27015.json").exists());
+    }
+}
@@ -1,16 +1,32 @@
-use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use format::Format;
+use ns2_stat::config::Config;
+use ns2_stat::filter::GameFilter;
+use ns2_stat::hive_skill::HiveSkillHistory;
 use ns2_stat::input_types::GameStats;
-use ns2_stat::{GameIterator, Map, NS2Stats, summarize_game};
-use rayon::prelude::*;
-
-use table::Alignment;
+use ns2_stat::load::{self, ErrorChain, GameFile, LoadOptions, OnError};
+use ns2_stat::prediction::{Backtest, WinModel};
+use ns2_stat::{summarize_game, GameId, NS2Stats, WinningTeam};
 
+mod anonymize;
+mod chart;
+#[cfg(feature = "sqlite")]
+mod database;
+mod export;
+mod format;
+mod game;
 mod helpers;
+mod import;
+mod player;
+mod sessions;
+mod stats;
 mod table;
+mod tables;
 mod teams;
+mod tui;
+mod validate;
 
 #[derive(Parser)]
 struct CliArgs {
@@ -18,135 +34,296 @@ struct CliArgs {
     #[clap(default_value = "test_data")]
     data_path: PathBuf,
 
-    /// Show team suggestions
-    #[clap(short, long, num_args = 1..)]
-    teams: Option<Vec<String>>,
-    #[clap(long, requires = "teams")]
-    marine_com: Option<String>,
-    #[clap(long, requires = "teams")]
-    alien_com: Option<String>,
+    /// The path for the game data, instead of the argument, e.g. after the command
+    #[clap(short = 'd', long = "data-path", global = true, value_name = "DATA_PATH")]
+    data_path_option: Option<PathBuf>,
+
+    /// The output format
+    #[clap(short, long, global = true, value_enum, default_value_t)]
+    format: Format,
+
+    /// The file to cache parsed games in, by default in the cache directory of the user
+    #[clap(long, global = true)]
+    cache: Option<PathBuf>,
+
+    /// Parse all games instead of using the cache
+    #[clap(long, global = true, conflicts_with = "cache")]
+    no_cache: bool,
+
+    /// The configuration with player merges and exclusions, by default `ns2-stat.toml` in the data path
+    #[clap(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Skip game files that can't be loaded with a warning, instead of failing
+    #[clap(long, global = true)]
+    skip_invalid: bool,
+
+    /// Read the games from this SQLite database instead of the data path, see the `store` command
+    #[cfg(feature = "sqlite")]
+    #[clap(long, global = true)]
+    database: Option<PathBuf>,
+
+    #[clap(flatten)]
+    filters: Filters,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
 }
 
-struct UserRow {
-    name: String,
-    kd: f32,
-    kda: f32,
-    games: u32,
-    commander: u32,
-    avg_score: f32,
-    accuracy: f32,
+// Options that select the games, shared by all subcommands.
+#[derive(Args)]
+struct Filters {
+    /// Only include games from this Unix time on
+    #[clap(long, global = true)]
+    from: Option<u32>,
+    /// Only include games up to this Unix time
+    #[clap(long, global = true)]
+    to: Option<u32>,
+    /// Only include games on this map
+    #[clap(long, global = true)]
+    map: Option<String>,
+    /// Only include games with this player
+    #[clap(long, global = true)]
+    player: Option<String>,
+    /// Only include games on this server, given by IP, `<ip>:<port>` or name
+    #[clap(long, global = true)]
+    server: Option<String>,
+    /// Only include games that lasted at least this many seconds
+    #[clap(long, global = true)]
+    min_length: Option<f32>,
+    /// Only include games won by this team, `none` for games without a winner
+    #[clap(long, global = true, value_enum)]
+    winner: Option<Winner>,
+    /// Also include bot games and games with too few players
+    #[clap(long, global = true)]
+    all: bool,
 }
 
-struct MapRow {
-    map: String,
-    marine_wr: f32,
-    total_games: u32,
+#[derive(Clone, Copy, ValueEnum)]
+enum Winner {
+    Marines,
+    Aliens,
+    None,
 }
 
-fn print_stats(stats: NS2Stats) {
-    let mut users = stats
-        .users
-        .into_iter()
-        .filter_map(|(name, user)| {
-            if user.games.total > 2 {
-                Some(UserRow {
-                    name,
-                    kd: user.kd().total,
-                    kda: user.kda().total,
-                    games: user.games.total,
-                    commander: user.commander.total,
-                    avg_score: user.average_score().total,
-                    accuracy: user.accuracy().total,
-                })
-            } else {
-                None
-            }
-        })
-        .collect::<Vec<_>>();
-    users.sort_by(|user1, user2| f32::total_cmp(&user1.avg_score, &user2.avg_score).reverse());
-    table::print_table(
-        ["NAME", "KD", "KDA", "GAMES", "COMMANDER", "AVG SCORE", "ACCURACY"],
-        [
-            Alignment::Left,
-            Alignment::Right,
-            Alignment::Right,
-            Alignment::Right,
-            Alignment::Right,
-            Alignment::Right,
-            Alignment::Right,
-        ],
-        &users,
-        |UserRow {
-             name,
-             kd,
-             kda,
-             games,
-             commander,
-             avg_score,
-             accuracy,
-         }| row!["{name}", "{kd:.2}", "{kda:.2}", "{games}", "{commander}", "{avg_score:.2}", "{accuracy:.2}"],
-    );
-
-    println!("\n\n");
-
-    let marine_wr = stats.marine_wins as f32 * 100f32 / stats.total_games as f32;
-    println!("MARINE WR: {marine_wr:.2}%");
-
-    println!();
-
-    let mut kvp = stats
-        .maps
-        .into_iter()
-        .map(|(map, Map { total_games, marine_wins, .. })| {
-            let marine_wr = marine_wins as f32 * 100f32 / total_games as f32;
-            MapRow { map, marine_wr, total_games }
-        })
-        .collect::<Vec<_>>();
-    kvp.sort_by(|map1, map2| f32::total_cmp(&map1.marine_wr, &map2.marine_wr).reverse());
-    table::print_table(
-        ["MAP", "MARINE WR", "TOTAL ROUNDS"],
-        [Alignment::Left, Alignment::Right, Alignment::Right],
-        &kvp,
-        |MapRow { map, marine_wr, total_games }| row!["{map}", "{marine_wr:.2}%", "{total_games} rounds"],
-    );
-
-    println!();
-
-    let total_games = stats.total_games;
-    println!("TOTAL GAMES: {total_games}");
+impl From<Winner> for WinningTeam {
+    fn from(winner: Winner) -> Self {
+        match winner {
+            Winner::Marines => WinningTeam::Marines,
+            Winner::Aliens => WinningTeam::Aliens,
+            Winner::None => WinningTeam::None,
+        }
+    }
 }
 
-fn load_data<P: AsRef<std::path::Path>>(data: P) -> Result<Vec<GameStats>, String> {
-    let data = data.as_ref();
-    let mut paths = Vec::new();
-    for entry in fs::read_dir(data).map_err(|e| format!("failed to read directory `{}`\n{}", data.display(), e))? {
-        let path = entry.map_err(|e| format!("{}", e))?.path();
-        if path.is_file() && path.extension().unwrap_or_default() == "json" {
-            paths.push(path)
+impl From<Filters> for GameFilter {
+    fn from(filters: Filters) -> Self {
+        Self {
+            from: filters.from,
+            to: filters.to,
+            map: filters.map,
+            player: filters.player,
+            server: filters.server,
+            min_length: filters.min_length,
+            winner: filters.winner.map(WinningTeam::from),
+            genuine: !filters.all,
         }
     }
+}
 
-    paths
-        .into_par_iter()
-        .map(|path| {
-            let data = fs::read_to_string(&path).map_err(|e| format!("failed to read `{}`\n{}", path.display(), e))?;
-            serde_json::from_str(&data).map_err(|e| format!("failed to parse `{}`\n{}", path.display(), e))
-        })
-        .collect()
+#[derive(Subcommand)]
+enum Command {
+    /// Show the player leaderboard (default)
+    Stats(stats::LeaderboardArgs),
+    /// Show the stats of one player, the name is matched fuzzily
+    Player {
+        name: String,
+        /// The number of recent games to show
+        #[clap(long, default_value = "5")]
+        recent: usize,
+        /// The number of latest games to compute the form from
+        #[clap(long, default_value = "10")]
+        form: usize,
+    },
+    /// Show the marine win rate per map
+    Maps,
+    /// Show one game, selected by `latest`, its ID, its index or its round date
+    Game { game: String },
+    /// Suggest balanced teams
+    Teams(teams::TeamsArgs),
+    /// Show the play sessions
+    Sessions {
+        /// The longest break between two games of the same session, in minutes
+        #[clap(long, default_value = "120")]
+        gap: u32,
+    },
+    /// Check all game files and report problems
+    Validate,
+    /// Copy new game files from a directory into the data path, skipping duplicates and invalid files
+    Import {
+        /// The directory with the new game files, e.g. the one the server writes to
+        source: PathBuf,
+        /// Only report what would be imported
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Store the games at the data path in a SQLite database, skipping games that are already stored
+    #[cfg(feature = "sqlite")]
+    Store {
+        /// The database file, created if it doesn't exist
+        database: PathBuf,
+    },
+    /// Export the game summaries as JSON, regardless of the output format
+    Export {
+        /// The output file, stdout if omitted
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Write the games with pseudonyms instead of Steam IDs, player names and servers, for publishing them
+    Anonymize {
+        /// The directory to write the game files to
+        output: PathBuf,
+        /// The secret the pseudonyms are derived from, the same salt gives the same pseudonyms in later exports
+        #[clap(long, env = "NS2_STAT_SALT", hide_env_values = true)]
+        salt: String,
+    },
+    /// Write the games as tables for data analysis, with a row per player, weapon, kill, building and research
+    Tables {
+        /// The directory to write a file per table to
+        directory: PathBuf,
+        /// The file format of the tables
+        #[clap(long, value_enum, default_value_t)]
+        file_format: tables::FileFormat,
+    },
+    /// Browse the stats interactively
+    Tui,
+    /// Evaluate the win prediction on the newest games, after fitting it on the rest
+    Backtest {
+        /// The fraction of the games to fit the model on, between 0 and 1
+        #[clap(long, default_value = "0.5", value_parser = parse_fraction)]
+        train_fraction: f32,
+    },
 }
 
-fn main() {
-    let args = CliArgs::parse();
+/// Loads all games, sorted by their ID. Files with the same content are only loaded once.
+/// With a cache file, only new and changed files are parsed. Skipped files are printed as warnings.
+fn load_data<P: AsRef<Path>>(data: P, options: &LoadOptions) -> Result<Vec<(GameId, GameStats)>, String> {
+    let games = load::load_with(data, options).map_err(|e| format!("{:#}", ErrorChain(&e)))?;
+    for err in &games.rejected {
+        eprintln!("Warning: {}", ErrorChain(err));
+    }
+    Ok(games.games.into_iter().collect())
+}
+
+/// Reads the game files at `path`, nothing if it doesn't exist.
+fn read_files(path: &Path) -> Result<Vec<GameFile>, String> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    load::read_files(path).map_err(|e| format!("{:#}", ErrorChain(&e)))
+}
+
+/// Parses a fraction strictly between 0 and 1.
+fn parse_fraction(s: &str) -> Result<f32, String> {
+    let fraction = s.parse::<f32>().map_err(|e| e.to_string())?;
+    if fraction > 0.0 && fraction < 1.0 {
+        Ok(fraction)
+    } else {
+        Err("the fraction has to be between 0 and 1".to_owned())
+    }
+}
+
+fn run(args: CliArgs) -> Result<(), String> {
+    let data_path = args.data_path_option.unwrap_or(args.data_path);
+    let command = args.command.unwrap_or_else(|| Command::Stats(Default::default()));
+    if let Command::Validate = command {
+        let (report, errors) = validate::validate(load::read_files(&data_path).map_err(|e| format!("{:#}", ErrorChain(&e)))?);
+        report.print(args.format);
+        return if errors > 0 { Err(format!("{} files are invalid", errors)) } else { Ok(()) };
+    }
+    if let Command::Import { source, dry_run } = &command {
+        let sources = load::read_files(source).map_err(|e| format!("{:#}", ErrorChain(&e)))?;
+        import::import(&data_path, read_files(&data_path)?, sources, *dry_run)?.print(args.format);
+        return Ok(());
+    }
+
+    let filter = GameFilter::from(args.filters);
+    let config = Config::load_or_default(args.config.as_ref(), &data_path).map_err(|e| format!("{:#}", ErrorChain(&e)))?;
+    let options = LoadOptions {
+        cache: if args.no_cache {
+            None
+        } else {
+            args.cache.or_else(|| load::default_cache_path(&data_path))
+        },
+        on_error: if args.skip_invalid { OnError::Collect } else { OnError::Fail },
+    };
+    #[cfg(feature = "sqlite")]
+    if let Command::Store { database } = &command {
+        database::store(database, &load_data(&data_path, &options)?)?.print(args.format);
+        return Ok(());
+    }
+    #[cfg(feature = "sqlite")]
+    let mut game_stats = match &args.database {
+        // the player is matched after applying the configuration, since it can rename players
+        Some(database) => database::load(
+            database,
+            &GameFilter {
+                player: None,
+                ..filter.clone()
+            },
+        )?,
+        None => load_data(&data_path, &options)?,
+    };
+    #[cfg(not(feature = "sqlite"))]
+    let mut game_stats = load_data(&data_path, &options)?;
+    game_stats.retain(|(id, _)| !config.is_excluded(id));
+    for (_, game) in &mut game_stats {
+        config.apply_to_game(game);
+    }
+    let games = game_stats.iter().filter(|(_, game)| filter.matches(game)).collect::<Vec<_>>();
+    let stats = || NS2Stats::compute(games.iter().map(|(_, game)| game));
+    let report = match command {
+        Command::Stats(leaderboard_args) => {
+            let hive_skills = HiveSkillHistory::by_name(HiveSkillHistory::compute(games.iter().map(|(_, game)| game)));
+            stats::leaderboard(stats(), hive_skills, &leaderboard_args)
+        }
+        Command::Player { name, recent, form } => player::player(&stats(), &games, &name, recent, form)?,
+        Command::Maps => stats::maps(stats()),
+        Command::Game { game } => game::game(game::select_game(&games, &game)?),
+        Command::Teams(teams_args) => {
+            let model = WinModel::fit(games.iter().map(|(_, game)| game));
+            teams::suggest_teams(
+                &model,
+                &stats(),
+                games.iter().map(|(id, game)| summarize_game(id, game)).collect(),
+                teams_args.players.clone(),
+                teams_args.constraints(),
+                teams_args.suggestions,
+                teams_args.recent,
+            )?
+        }
+        Command::Sessions { gap } => sessions::sessions(&games.iter().map(|(_, game)| game).collect::<Vec<_>>(), gap),
+        Command::Validate | Command::Import { .. } => unreachable!(),
+        #[cfg(feature = "sqlite")]
+        Command::Store { .. } => unreachable!(),
+        Command::Export { output } => return export::export(&games, output.as_deref()),
+        Command::Anonymize { output, salt } => anonymize::anonymize(&games, &data_path, &output, &salt)?,
+        Command::Tables { directory, file_format } => tables::export(&games, &directory, file_format)?,
+        Command::Tui => return tui::run(games).map_err(|e| format!("terminal error\n{}", e)),
+        Command::Backtest { train_fraction } => {
+            let backtest = Backtest::run(games.iter().map(|(_, game)| game), train_fraction)
+                .ok_or("not enough games to both fit and evaluate the model, try another training fraction")?;
+            stats::backtest(backtest)
+        }
+    };
+    report.print(args.format);
+    Ok(())
+}
 
-    let game_stats = load_data(args.data_path).unwrap_or_else(|err| {
+fn main() {
+    if let Err(err) = run(CliArgs::parse()) {
         eprintln!("Error: {}", err);
         std::process::exit(1);
-    });
-    let games = game_stats.iter().genuine();
-    if let Some(players) = args.teams {
-        teams::suggest_teams(games.map(summarize_game).collect(), players, args.marine_com, args.alien_com);
-    } else {
-        print_stats(NS2Stats::compute(games));
     }
 }
 
@@ -156,6 +333,29 @@ mod tests {
 
     #[test]
     fn test_data_parsable() {
-        load_data("../test_data").unwrap();
+        load_data("../test_data", &LoadOptions::default()).unwrap();
+    }
+
+    #[test]
+    fn data_path_argument_or_option() {
+        let data_path = |args: &[&str]| {
+            let args = CliArgs::try_parse_from(["ns2-stat-cli"].iter().chain(args)).unwrap();
+            args.data_path_option.unwrap_or(args.data_path)
+        };
+        assert_eq!(data_path(&[]), PathBuf::from("test_data"));
+        assert_eq!(data_path(&["games", "maps"]), PathBuf::from("games"));
+        assert_eq!(data_path(&["maps", "-d", "games"]), PathBuf::from("games"));
+        assert_eq!(data_path(&["--data-path", "games"]), PathBuf::from("games"));
+    }
+
+    #[test]
+    fn preferences_keep_the_players() {
+        let args = CliArgs::try_parse_from(["ns2-stat-cli", "teams", "--prefer-marines", "a,b", "c", "d", "--prefer-aliens", "c"]).unwrap();
+        let Some(Command::Teams(teams_args)) = args.command else {
+            panic!("expected the teams command")
+        };
+        assert_eq!(teams_args.players, ["c", "d"]);
+        assert_eq!(teams_args.prefer_marines, ["a", "b"]);
+        assert_eq!(teams_args.prefer_aliens, ["c"]);
     }
 }
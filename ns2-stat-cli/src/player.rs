@@ -0,0 +1,177 @@
+use ns2_stat::input_types::{GameStats, Team};
+use ns2_stat::player::{find_player, Form, MapRecord, PlayerProfile};
+use ns2_stat::{GameId, NS2Stats, Stat, User};
+
+use crate::chart::Chart;
+use crate::helpers;
+use crate::row;
+use crate::table::{Alignment, Report, Value};
+
+struct SideRow {
+    side: &'static str,
+    games: u32,
+    wins: u32,
+    kd: f32,
+    kda: f32,
+    avg_score: f32,
+    accuracy: f32,
+    commander: u32,
+    commander_wins: u32,
+}
+
+fn side_rows(user: &User, profile: &PlayerProfile) -> Vec<SideRow> {
+    let row = |side, get: fn(&Stat<u32>) -> u32, get_f32: fn(&Stat<f32>) -> f32| SideRow {
+        side,
+        games: get(&user.games),
+        wins: get(&user.wins),
+        kd: get_f32(&user.kd()),
+        kda: get_f32(&user.kda()),
+        avg_score: get_f32(&user.average_score()),
+        accuracy: get_f32(&user.accuracy()),
+        commander: get(&profile.commander_games),
+        commander_wins: get(&profile.commander_wins),
+    };
+    vec![
+        row("TOTAL", |stat| stat.total, |stat| stat.total),
+        row("MARINES", |stat| stat.marines, |stat| stat.marines),
+        row("ALIENS", |stat| stat.aliens, |stat| stat.aliens),
+    ]
+}
+
+fn map_table<'a>(report: &mut Report, title: &str, maps: impl Iterator<Item = &'a MapRecord>) {
+    report.table(
+        title,
+        ["MAP", "GAMES", "WR"],
+        [Alignment::Left, Alignment::Right, Alignment::Right],
+        maps.map(|map| row![&map.map_name, map.games, Value::percent(map.wins as f32 * 100.0 / map.games as f32)]),
+    );
+}
+
+/// The stats of the player that matches `query`, with the `recent` latest games and the form over the `form` latest games.
+pub fn player(stats: &NS2Stats, games: &[&(GameId, GameStats)], query: &str, recent: usize, form: usize) -> Result<Report, String> {
+    let name = find_player(stats.users.keys(), query).map_err(|candidates| match &candidates[..] {
+        [] => format!("no player matching `{}`", query),
+        _ => format!(
+            "`{}` matches multiple players: {}",
+            query,
+            helpers::format_with(candidates.iter(), ", ", |f, name| write!(f, "{}", name))
+        ),
+    })?;
+    let user = &stats.users[name];
+    let profile = PlayerProfile::compute(name, games.iter().map(|(id, game)| (id, game))).ok_or_else(|| format!("`{}` did not play any games", name))?;
+
+    let mut report = Report::new();
+    report.fields("Player", [("NAME", name.into())]);
+    report.table(
+        "Sides",
+        ["SIDE", "GAMES", "WINS", "KD", "KDA", "AVG SCORE", "ACCURACY", "COMMANDER", "COMMANDER WINS"],
+        [
+            Alignment::Left,
+            Alignment::Right,
+            Alignment::Right,
+            Alignment::Right,
+            Alignment::Right,
+            Alignment::Right,
+            Alignment::Right,
+            Alignment::Right,
+            Alignment::Right,
+        ],
+        side_rows(user, &profile).into_iter().map(
+            |SideRow {
+                 side,
+                 games,
+                 wins,
+                 kd,
+                 kda,
+                 avg_score,
+                 accuracy,
+                 commander,
+                 commander_wins,
+             }| row![side, games, wins, kd, kda, avg_score, accuracy, commander, commander_wins],
+        ),
+    );
+
+    report.table(
+        "Recent games",
+        ["DATE", "MAP", "TEAM", "RESULT", "K", "D", "A", "SCORE"],
+        [
+            Alignment::Left,
+            Alignment::Left,
+            Alignment::Left,
+            Alignment::Left,
+            Alignment::Right,
+            Alignment::Right,
+            Alignment::Right,
+            Alignment::Right,
+        ],
+        profile.recent_games(recent).map(|game| {
+            let team = match (game.team, game.commander) {
+                (Team::Marines, false) => "Marines",
+                (Team::Marines, true) => "Marines [C]",
+                (Team::Aliens, false) => "Aliens",
+                (Team::Aliens, true) => "Aliens [C]",
+            };
+            let result = if game.won { "won" } else { "lost" };
+            row![
+                helpers::format_date(game.round_date),
+                &game.map_name,
+                team,
+                result,
+                game.stats.kills,
+                game.stats.deaths,
+                game.stats.assists,
+                game.stats.score
+            ]
+        }),
+    );
+
+    let best = profile.maps.len().min(3);
+    map_table(&mut report, "Best maps", profile.maps[..best].iter());
+    if profile.maps.len() > best {
+        map_table(&mut report, "Worst maps", profile.maps[best.max(profile.maps.len() - 3)..].iter().rev());
+    }
+
+    report.table(
+        "Weapons",
+        ["WEAPON", "KILLS", "ACCURACY"],
+        [Alignment::Left, Alignment::Right, Alignment::Right],
+        profile.weapons.iter().take(5).map(|weapon| {
+            let attacks = weapon.hits + weapon.misses;
+            let accuracy = (attacks > 0).then(|| Value::percent(weapon.hits as f32 * 100.0 / attacks as f32));
+            row![&weapon.weapon, weapon.kills, accuracy]
+        }),
+    );
+
+    let total_time = profile.lifeforms.iter().map(|lifeform| lifeform.time).sum::<f32>();
+    report.table(
+        "Lifeforms",
+        ["LIFEFORM", "TIME", "SHARE"],
+        [Alignment::Left, Alignment::Right, Alignment::Right],
+        profile.lifeforms.iter().map(|lifeform| {
+            row![
+                format!("{:?}", lifeform.lifeform),
+                helpers::format_duration(lifeform.time),
+                (total_time > 0.0).then(|| Value::percent(lifeform.time * 100.0 / total_time))
+            ]
+        }),
+    );
+
+    let forms = [
+        (format!("LAST {}", form), profile.form(form)),
+        ("ALL".to_owned(), profile.form(profile.games.len())),
+    ];
+    report.table(
+        "Form",
+        ["PERIOD", "GAMES", "WR", "KD", "AVG SCORE"],
+        [Alignment::Left, Alignment::Right, Alignment::Right, Alignment::Right, Alignment::Right],
+        forms
+            .into_iter()
+            .map(|(period, form)| row![period, form.games, form.win_rate().map(Value::percent), form.kd, form.average_score]),
+    );
+
+    // the trends are rolling averages over `form` games
+    let rolling = profile.rolling_form(form);
+    report.chart("Win rate trend", Chart::Sparkline(rolling.iter().filter_map(Form::win_rate).collect()));
+    report.chart("KD trend", Chart::Sparkline(rolling.iter().filter_map(|form| form.kd).collect()));
+    Ok(report)
+}
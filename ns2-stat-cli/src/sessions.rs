@@ -0,0 +1,126 @@
+use std::collections::{HashMap, HashSet};
+
+use ns2_stat::input_types::{GameStats, WinningTeam};
+
+use crate::helpers;
+use crate::row;
+use crate::table::{Alignment, Report};
+
+/// Consecutive games on one server where the break between two games is at most the session gap.
+struct Session {
+    server: String,
+    start: u32,
+    end: u32,
+    games: u32,
+    players: usize,
+    marine_wins: u32,
+    alien_wins: u32,
+}
+
+/// Splits the games into sessions per server, sorted by their start.
+fn split_sessions(games: &[&GameStats], gap: u32) -> Vec<Session> {
+    let mut sessions = Vec::<Session>::new();
+    let mut players = Vec::<HashSet<&str>>::new();
+    // the index of the latest session of each server
+    let mut latest = HashMap::<_, usize>::new();
+    for game in games {
+        let round_info = &game.round_info;
+        // the round date is the end of the round
+        let end = round_info.round_date;
+        let start = end.saturating_sub(round_info.round_length as u32);
+        let server = (game.server_info.ip.as_str(), game.server_info.port);
+        let index = match latest.get(&server) {
+            Some(&index) if start <= sessions[index].end + gap => {
+                let session = &mut sessions[index];
+                session.start = session.start.min(start);
+                session.end = session.end.max(end);
+                session.games += 1;
+                index
+            }
+            _ => {
+                sessions.push(Session {
+                    server: game.server_info.name.clone(),
+                    start,
+                    end,
+                    games: 1,
+                    players: 0,
+                    marine_wins: 0,
+                    alien_wins: 0,
+                });
+                players.push(HashSet::new());
+                latest.insert(server, sessions.len() - 1);
+                sessions.len() - 1
+            }
+        };
+        let session = &mut sessions[index];
+        match round_info.winning_team {
+            WinningTeam::Marines => session.marine_wins += 1,
+            WinningTeam::Aliens => session.alien_wins += 1,
+            WinningTeam::None => {}
+        }
+        players[index].extend(game.player_stats.values().map(|player_stat| player_stat.player_name.as_str()));
+        session.players = players[index].len();
+    }
+    sessions.sort_by_key(|session| session.start);
+    sessions
+}
+
+/// The play sessions. The games have to be sorted chronologically.
+pub fn sessions(games: &[&GameStats], gap_minutes: u32) -> Report {
+    let mut report = Report::new();
+    report.table(
+        "Sessions",
+        ["START", "SERVER", "GAMES", "DURATION", "PLAYERS", "MARINE WINS", "ALIEN WINS"],
+        [
+            Alignment::Left,
+            Alignment::Left,
+            Alignment::Right,
+            Alignment::Right,
+            Alignment::Right,
+            Alignment::Right,
+            Alignment::Right,
+        ],
+        split_sessions(games, gap_minutes * 60).into_iter().map(|session| {
+            row![
+                helpers::format_date(session.start),
+                session.server,
+                session.games,
+                helpers::format_duration((session.end - session.start) as f32),
+                session.players,
+                session.marine_wins,
+                session.alien_wins
+            ]
+        }),
+    );
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sessions_per_server() {
+        let games = crate::load_data("../test_data", &Default::default()).unwrap();
+        let mut first = games[0].1.clone();
+        first.round_info.round_date = 10_000;
+        first.round_info.round_length = 1_000.0;
+        let mut second = first.clone();
+        second.round_info.round_date = 11_500;
+        second.round_info.round_length = 800.0;
+
+        // the second round started 700 seconds after the first one ended
+        let sessions = split_sessions(&[&first, &second], 600);
+        assert_eq!(sessions.len(), 2);
+        let sessions = split_sessions(&[&first, &second], 700);
+        assert_eq!(sessions.len(), 1);
+        assert_eq!((sessions[0].start, sessions[0].end, sessions[0].games), (9_000, 11_500, 2));
+
+        second.server_info.port += 1;
+        let sessions = split_sessions(&[&first, &second], 700);
+        assert_eq!(
+            sessions.iter().map(|session| (session.start, session.end)).collect::<Vec<_>>(),
+            [(9_000, 10_000), (10_700, 11_500)]
+        );
+    }
+}
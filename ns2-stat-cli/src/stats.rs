@@ -0,0 +1,221 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use clap::{Args, ValueEnum};
+use ns2_stat::hive_skill::HiveSkillHistory;
+use ns2_stat::prediction::Backtest;
+use ns2_stat::{Map, NS2Stats, Stat, User};
+
+use crate::chart::Chart;
+use crate::row;
+use crate::table::{Alignment, Report, Value};
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum Side {
+    #[default]
+    Total,
+    Marines,
+    Aliens,
+}
+
+impl Side {
+    fn get<T: Copy>(self, stat: Stat<T>) -> T {
+        match self {
+            Side::Total => stat.total,
+            Side::Marines => stat.marines,
+            Side::Aliens => stat.aliens,
+        }
+    }
+}
+
+/// A column of the leaderboard.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Metric {
+    Games,
+    Wins,
+    WinRate,
+    Commander,
+    Kills,
+    Deaths,
+    Assists,
+    Kd,
+    Kda,
+    AvgScore,
+    Accuracy,
+    Hits,
+    Misses,
+    HiveSkill,
+}
+
+impl Metric {
+    pub fn title(self) -> &'static str {
+        match self {
+            Metric::Games => "GAMES",
+            Metric::Wins => "WINS",
+            Metric::WinRate => "WR",
+            Metric::Commander => "COMMANDER",
+            Metric::Kills => "KILLS",
+            Metric::Deaths => "DEATHS",
+            Metric::Assists => "ASSISTS",
+            Metric::Kd => "KD",
+            Metric::Kda => "KDA",
+            Metric::AvgScore => "AVG SCORE",
+            Metric::Accuracy => "ACCURACY",
+            Metric::Hits => "HITS",
+            Metric::Misses => "MISSES",
+            Metric::HiveSkill => "HIVE SKILL",
+        }
+    }
+
+    pub fn value(self, user: &User, hive_skill: Option<Stat<u32>>, side: Side) -> Value {
+        match self {
+            Metric::Games => side.get(user.games).into(),
+            Metric::Wins => side.get(user.wins).into(),
+            Metric::WinRate => Value::percent(side.get(user.win_rate()) * 100.0),
+            Metric::Commander => side.get(user.commander).into(),
+            Metric::Kills => side.get(user.kills).into(),
+            Metric::Deaths => side.get(user.deaths).into(),
+            Metric::Assists => side.get(user.assists).into(),
+            Metric::Kd => side.get(user.kd()).into(),
+            Metric::Kda => side.get(user.kda()).into(),
+            Metric::AvgScore => side.get(user.average_score()).into(),
+            Metric::Accuracy => side.get(user.accuracy()).into(),
+            Metric::Hits => side.get(user.hits).into(),
+            Metric::Misses => side.get(user.misses).into(),
+            Metric::HiveSkill => hive_skill.map(|skill| side.get(skill)).into(),
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct LeaderboardArgs {
+    /// The column to sort by
+    #[clap(long, value_enum, default_value_t = Metric::AvgScore)]
+    sort: Metric,
+    /// Sort in ascending instead of descending order
+    #[clap(long)]
+    ascending: bool,
+    /// The columns to show, separated by commas
+    #[clap(long, value_enum, value_delimiter = ',', default_values_t = DEFAULT_COLUMNS)]
+    columns: Vec<Metric>,
+    /// Only show players with at least this many games on the side
+    #[clap(long, default_value = "3")]
+    min_games: u32,
+    /// Show the stats of this side
+    #[clap(long, value_enum, default_value_t)]
+    side: Side,
+}
+
+const DEFAULT_COLUMNS: [Metric; 7] = [
+    Metric::Kd,
+    Metric::Kda,
+    Metric::Games,
+    Metric::Commander,
+    Metric::AvgScore,
+    Metric::Accuracy,
+    Metric::HiveSkill,
+];
+
+impl Default for LeaderboardArgs {
+    fn default() -> Self {
+        Self {
+            sort: Metric::AvgScore,
+            ascending: false,
+            columns: DEFAULT_COLUMNS.to_vec(),
+            min_games: 3,
+            side: Side::Total,
+        }
+    }
+}
+
+struct MapRow {
+    map: String,
+    marine_wr: f32,
+    total_games: u32,
+}
+
+/// Compares two values for sorting. Missing values and NaN are always sorted last.
+pub fn compare(value1: &Value, value2: &Value, ascending: bool) -> Ordering {
+    let number = |value: &Value| value.as_f32().filter(|n| !n.is_nan());
+    match (number(value1), number(value2)) {
+        (Some(n1), Some(n2)) if ascending => n1.total_cmp(&n2),
+        (Some(n1), Some(n2)) => n2.total_cmp(&n1),
+        (n1, n2) => n2.is_some().cmp(&n1.is_some()),
+    }
+}
+
+pub fn leaderboard(stats: NS2Stats, hive_skills: HashMap<String, HiveSkillHistory>, args: &LeaderboardArgs) -> Report {
+    let mut users = stats
+        .users
+        .iter()
+        .filter(|(_, user)| args.side.get(user.games) >= args.min_games)
+        .map(|(name, user)| {
+            let hive_skill = hive_skills.get(name).and_then(|history| history.current());
+            let values = args.columns.iter().map(|metric| metric.value(user, hive_skill, args.side)).collect::<Vec<_>>();
+            (name, args.sort.value(user, hive_skill, args.side), values)
+        })
+        .collect::<Vec<_>>();
+    users.sort_by(|(name1, key1, _), (name2, key2, _)| compare(key1, key2, args.ascending).then_with(|| name1.cmp(name2)));
+
+    let mut report = Report::new();
+    let columns = args.columns.iter().map(|metric| (metric.title().to_owned(), Alignment::Right));
+    report.dynamic_table(
+        "Leaderboard",
+        [("NAME".to_owned(), Alignment::Left)].into_iter().chain(columns).collect(),
+        users
+            .into_iter()
+            .map(|(name, _, values)| [Value::from(name)].into_iter().chain(values).collect())
+            .collect(),
+    );
+    report.fields("Summary", [("TOTAL GAMES", stats.total_games.into())]);
+    report
+}
+
+pub fn maps(stats: NS2Stats) -> Report {
+    let mut report = Report::new();
+    let marine_wr = stats.marine_wins as f32 * 100f32 / stats.total_games as f32;
+    report.fields("Summary", [("MARINE WR", Value::percent(marine_wr))]);
+
+    let mut kvp = stats
+        .maps
+        .into_iter()
+        .map(|(map, Map { total_games, marine_wins, .. })| {
+            let marine_wr = marine_wins as f32 * 100f32 / total_games as f32;
+            MapRow { map, marine_wr, total_games }
+        })
+        .collect::<Vec<_>>();
+    kvp.sort_by(|map1, map2| f32::total_cmp(&map1.marine_wr, &map2.marine_wr).reverse());
+    let bars = kvp.iter().map(|row| (row.map.clone(), Value::percent(row.marine_wr))).collect();
+    report.table(
+        "Maps",
+        ["MAP", "MARINE WR", "TOTAL ROUNDS"],
+        [Alignment::Left, Alignment::Right, Alignment::Right],
+        kvp.into_iter()
+            .map(|MapRow { map, marine_wr, total_games }| row![map, Value::percent(marine_wr), total_games]),
+    );
+    report.chart("Marine win rate", Chart::Bars { bars, max: 100.0 });
+    report
+}
+
+pub fn backtest(backtest: Backtest) -> Report {
+    let mut report = Report::new();
+    report.fields(
+        "Summary",
+        [
+            ("TRAINING GAMES", backtest.training_games.into()),
+            ("TEST GAMES", backtest.test_games.into()),
+            ("ACCURACY", Value::percent(backtest.accuracy * 100.0)),
+            ("BRIER SCORE", Value::number(backtest.brier_score, 3)),
+        ],
+    );
+    report.table(
+        "Calibration",
+        ["PREDICTED MARINE WR", "OBSERVED MARINE WR", "GAMES"],
+        [Alignment::Right, Alignment::Right, Alignment::Right],
+        backtest
+            .calibration
+            .iter()
+            .map(|bin| row![Value::percent(bin.predicted * 100.0), Value::percent(bin.observed * 100.0), bin.games]),
+    );
+    report
+}
@@ -1,7 +1,13 @@
+use std::fmt;
+
+use crate::chart::{Chart, Style};
+use crate::format::Format;
+
+/// Creates a table row, converting each element into a [`Value`].
 #[macro_export]
 macro_rules! row {
-    ($($e:literal),*$(,)?) => {
-        [$(format!($e)),*]
+    ($($e:expr),*$(,)?) => {
+        [$($crate::table::Value::from($e)),*]
     }
 }
 
@@ -13,31 +19,193 @@ pub enum Alignment {
     Right,
 }
 
-pub fn print_table<T, const N: usize>(titles: [&str; N], alignments: [Alignment; N], table: &[T], formatter: impl Fn(&T) -> [String; N]) {
-    let mut lengths = [0; N]; // `lengths[i]` is the length of the ith column
-    let rows = table.iter().map(formatter).collect::<Vec<_>>();
-    for i in 0..N {
-        lengths[i] = std::cmp::max(
-            titles[i].len(),
-            rows.iter().map(|row| row[i].len()).max().unwrap_or(0),
-        );
+/// A table cell or the value of a field.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Text(String),
+    Integer(i64),
+    /// A number and how many decimals are shown.
+    Number(f32, usize),
+    /// A percentage, e.g. `50.0` for 50%.
+    Percent(f32),
+    Missing,
+}
+
+impl Value {
+    pub fn number(value: f32, decimals: usize) -> Self {
+        Value::Number(value, decimals)
     }
 
-    for i in 0..N {
-        print!("{:width$}    ", titles[i], width = lengths[i]);
+    pub fn percent(value: f32) -> Self {
+        Value::Percent(value)
     }
-    println!();
-    for row in rows {
-        for i in 0..N {
-            let content = &row[i];
-            let alignment = alignments[i];
-            let len = lengths[i];
-            match alignment {
-                Alignment::Left => print!("{:<width$}    ", content, width = len),
-                Alignment::Center => print!("{:^width$}    ", content, width = len),
-                Alignment::Right => print!("{:>width$}    ", content, width = len),
+
+    /// The numeric value, `None` for text and missing values.
+    pub fn as_f32(&self) -> Option<f32> {
+        match *self {
+            Value::Integer(n) => Some(n as f32),
+            Value::Number(n, _) | Value::Percent(n) => Some(n),
+            Value::Text(_) | Value::Missing => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Text(text) => write!(f, "{}", text),
+            Value::Integer(n) => write!(f, "{}", n),
+            Value::Number(n, decimals) => write!(f, "{:.*}", decimals, n),
+            Value::Percent(n) => write!(f, "{:.2}%", n),
+            Value::Missing => write!(f, "-"),
+        }
+    }
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Self {
+        Value::Text(text)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Value::Text(text.to_owned())
+    }
+}
+
+impl From<&String> for Value {
+    fn from(text: &String) -> Self {
+        Value::Text(text.clone())
+    }
+}
+
+impl From<u32> for Value {
+    fn from(n: u32) -> Self {
+        Value::Integer(n.into())
+    }
+}
+
+impl From<usize> for Value {
+    fn from(n: usize) -> Self {
+        Value::Integer(n as i64)
+    }
+}
+
+/// Numbers are shown with two decimals, use [`Value::number`] for more or less.
+impl From<f32> for Value {
+    fn from(n: f32) -> Self {
+        Value::Number(n, 2)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Missing, Into::into)
+    }
+}
+
+pub enum Section {
+    Table {
+        columns: Vec<(String, Alignment)>,
+        rows: Vec<Vec<Value>>,
+    },
+    /// Named values, like a table with a single row.
+    Fields(Vec<(String, Value)>),
+    Chart(Chart),
+}
+
+/// The output of a CLI view, a sequence of titled sections that can be printed in any [`Format`].
+#[derive(Default)]
+pub struct Report {
+    pub sections: Vec<(String, Section)>,
+}
+
+impl Report {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn table<const N: usize>(&mut self, title: &str, columns: [&str; N], alignments: [Alignment; N], rows: impl IntoIterator<Item = [Value; N]>) {
+        let columns = columns.into_iter().map(|column| column.to_owned()).zip(alignments).collect();
+        self.dynamic_table(title, columns, rows.into_iter().map(Vec::from).collect());
+    }
+
+    /// A table where the columns are only known at runtime. Every row needs a value for each column.
+    pub fn dynamic_table(&mut self, title: &str, columns: Vec<(String, Alignment)>, rows: Vec<Vec<Value>>) {
+        assert!(rows.iter().all(|row| row.len() == columns.len()));
+        self.sections.push((title.to_owned(), Section::Table { columns, rows }));
+    }
+
+    pub fn fields<'a>(&mut self, title: &str, fields: impl IntoIterator<Item = (&'a str, Value)>) {
+        let fields = fields.into_iter().map(|(name, value)| (name.to_owned(), value)).collect();
+        self.sections.push((title.to_owned(), Section::Fields(fields)));
+    }
+
+    pub fn chart(&mut self, title: &str, chart: Chart) {
+        self.sections.push((title.to_owned(), Section::Chart(chart)));
+    }
+
+    pub fn print(&self, format: Format) {
+        print!("{}", format.render(self));
+    }
+}
+
+/// Renders the report as aligned plain text. Tables and charts are preceded by their title.
+pub fn render_text(report: &Report, style: Style) -> String {
+    let mut out = String::new();
+    for (i, (title, section)) in report.sections.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        match section {
+            Section::Table { columns, rows } => {
+                out += &title.to_uppercase();
+                out.push('\n');
+                render_table(&mut out, columns, rows);
+            }
+            Section::Fields(fields) => {
+                for (name, value) in fields {
+                    out += &format!("{}: {}\n", name, value);
+                }
+            }
+            Section::Chart(chart) => {
+                out += &title.to_uppercase();
+                out.push('\n');
+                out += &chart.render(style);
             }
         }
-        println!();
+    }
+    out
+}
+
+fn render_table(out: &mut String, columns: &[(String, Alignment)], rows: &[Vec<Value>]) {
+    let rows = rows
+        .iter()
+        .map(|row| row.iter().map(|value| value.to_string()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    // `lengths[i]` is the length of the ith column
+    let lengths = columns
+        .iter()
+        .enumerate()
+        .map(|(i, (title, _))| {
+            let max_length = rows.iter().map(|row| row[i].chars().count()).max().unwrap_or(0);
+            title.chars().count().max(max_length)
+        })
+        .collect::<Vec<_>>();
+
+    for ((title, _), &len) in columns.iter().zip(&lengths) {
+        *out += &format!("{:width$}    ", title, width = len);
+    }
+    out.push('\n');
+    for row in rows {
+        for ((content, &(_, alignment)), &len) in row.iter().zip(columns).zip(&lengths) {
+            *out += &match alignment {
+                Alignment::Left => format!("{:<width$}    ", content, width = len),
+                Alignment::Center => format!("{:^width$}    ", content, width = len),
+                Alignment::Right => format!("{:>width$}    ", content, width = len),
+            };
+        }
+        out.push('\n');
     }
 }
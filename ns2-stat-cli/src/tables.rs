@@ -0,0 +1,532 @@
+//! Flattens the games into tidy tables for data analysis, with one row per observation and stable column names.
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use clap::ValueEnum;
+use ns2_stat::input_types::{Building, GameStats, KillFeed, PlayerStat, PlayerTeamStats, Position, Research, SteamId, Team, Weapon, WinningTeam};
+use ns2_stat::GameId;
+
+use crate::format::csv_field;
+use crate::row;
+use crate::table::{Alignment, Report, Value};
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum FileFormat {
+    #[default]
+    Csv,
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+impl FileFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            FileFormat::Csv => "csv",
+            #[cfg(feature = "parquet")]
+            FileFormat::Parquet => "parquet",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Integer,
+    Float,
+    Boolean,
+    Text,
+}
+
+enum Cell {
+    Integer(i64),
+    Float(f32),
+    Boolean(bool),
+    Text(String),
+    Null,
+}
+
+/// A value that can be stored in a column.
+trait IntoCell {
+    const KIND: Kind;
+
+    fn into_cell(self) -> Cell;
+}
+
+macro_rules! integer_cell {
+    ($($t:ty),*) => {
+        $(impl IntoCell for $t {
+            const KIND: Kind = Kind::Integer;
+
+            fn into_cell(self) -> Cell {
+                Cell::Integer(self as i64)
+            }
+        })*
+    };
+}
+
+integer_cell!(u8, u16, u32, i32, i64, usize);
+
+impl IntoCell for f32 {
+    const KIND: Kind = Kind::Float;
+
+    fn into_cell(self) -> Cell {
+        Cell::Float(self)
+    }
+}
+
+impl IntoCell for bool {
+    const KIND: Kind = Kind::Boolean;
+
+    fn into_cell(self) -> Cell {
+        Cell::Boolean(self)
+    }
+}
+
+impl IntoCell for String {
+    const KIND: Kind = Kind::Text;
+
+    fn into_cell(self) -> Cell {
+        Cell::Text(self)
+    }
+}
+
+impl IntoCell for &str {
+    const KIND: Kind = Kind::Text;
+
+    fn into_cell(self) -> Cell {
+        Cell::Text(self.to_owned())
+    }
+}
+
+impl<T: IntoCell> IntoCell for Option<T> {
+    const KIND: Kind = T::KIND;
+
+    fn into_cell(self) -> Cell {
+        self.map_or(Cell::Null, T::into_cell)
+    }
+}
+
+struct Column<'a, T> {
+    name: String,
+    /// The type of the column, CSV files don't store it.
+    #[cfg_attr(not(feature = "parquet"), allow(dead_code))]
+    kind: Kind,
+    get: Box<dyn Fn(&T) -> Cell + 'a>,
+}
+
+fn column<'a, T, V: IntoCell>(name: impl Into<String>, get: impl Fn(&T) -> V + 'a) -> Column<'a, T> {
+    Column {
+        name: name.into(),
+        kind: V::KIND,
+        get: Box::new(move |row| get(row).into_cell()),
+    }
+}
+
+struct Table<'a, T> {
+    name: &'static str,
+    columns: Vec<Column<'a, T>>,
+    rows: Vec<T>,
+}
+
+#[derive(Clone, Copy)]
+struct Round<'a> {
+    id: &'a GameId,
+    game: &'a GameStats,
+}
+
+impl Round<'_> {
+    fn player_name(&self, steam_id: SteamId) -> Option<String> {
+        self.game.player_stats.get(&steam_id).map(|player| player.player_name.clone())
+    }
+
+    fn location_name(&self, location: Option<usize>) -> Option<String> {
+        location.and_then(|location| self.game.location_name(location)).map(|name| name.to_owned())
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Player<'a> {
+    round: Round<'a>,
+    steam_id: SteamId,
+    stats: &'a PlayerStat,
+}
+
+impl Player<'_> {
+    /// The team the player played on the longest, like in the stats.
+    fn team(&self) -> Team {
+        if self.stats.marines.time_played > self.stats.aliens.time_played {
+            Team::Marines
+        } else {
+            Team::Aliens
+        }
+    }
+
+    fn team_stats(&self, team: Team) -> &PlayerTeamStats {
+        match team {
+            Team::Marines => &self.stats.marines,
+            Team::Aliens => &self.stats.aliens,
+        }
+    }
+}
+
+struct PlayerWeapon<'a> {
+    player: Player<'a>,
+    name: &'a str,
+    weapon: &'a Weapon,
+}
+
+/// A kill, building or research event.
+struct RoundEvent<'a, E> {
+    round: Round<'a>,
+    event: &'a E,
+}
+
+fn team_name(team: Team) -> &'static str {
+    match team {
+        Team::Marines => "marines",
+        Team::Aliens => "aliens",
+    }
+}
+
+fn game_id<'a, T>(round: impl Fn(&T) -> Round + 'a) -> Column<'a, T> {
+    column("game_id", move |row| round(row).id.to_string())
+}
+
+/// The columns `<prefix>x`, `<prefix>y` and `<prefix>z` of a position.
+fn position<'a, T>(prefix: &str, get: impl Fn(&T) -> Option<&Position> + Copy + 'a) -> [Column<'a, T>; 3] {
+    [
+        column(format!("{}x", prefix), move |row| get(row).map(|position| position.x)),
+        column(format!("{}y", prefix), move |row| get(row).map(|position| position.y)),
+        column(format!("{}z", prefix), move |row| get(row).map(|position| position.z)),
+    ]
+}
+
+fn rounds<'a>(games: &[&'a (GameId, GameStats)]) -> Table<'a, Round<'a>> {
+    let columns = vec![
+        game_id(|round: &Round<'a>| *round),
+        column("round_date", |round: &Round| round.game.round_info.round_date),
+        column("map_name", |round: &Round| round.game.round_info.map_name.clone()),
+        column("round_length", |round: &Round| round.game.round_info.round_length),
+        column("winning_team", |round: &Round| match round.game.round_info.winning_team {
+            WinningTeam::None => "none",
+            WinningTeam::Marines => "marines",
+            WinningTeam::Aliens => "aliens",
+        }),
+        column("tournament_mode", |round: &Round| round.game.round_info.tournament_mode),
+        column("max_players_marines", |round: &Round| round.game.round_info.max_players_marines),
+        column("max_players_aliens", |round: &Round| round.game.round_info.max_players_aliens),
+        column("genuine", |round: &Round| round.game.is_genuine()),
+        column("server_name", |round: &Round| round.game.server_info.name.clone()),
+        column("server_ip", |round: &Round| round.game.server_info.ip.clone()),
+        column("server_port", |round: &Round| round.game.server_info.port),
+        column("build_number", |round: &Round| round.game.server_info.build_number),
+    ];
+    Table {
+        name: "rounds",
+        columns,
+        rows: games.iter().map(|(id, game)| Round { id, game }).collect(),
+    }
+}
+
+fn team_stats<'a>(team: Team) -> Vec<Column<'a, Player<'a>>> {
+    let name = |field: &str| format!("{}_{}", team_name(team), field);
+    vec![
+        column(name("kills"), move |player: &Player| player.team_stats(team).kills),
+        column(name("deaths"), move |player: &Player| player.team_stats(team).deaths),
+        column(name("assists"), move |player: &Player| player.team_stats(team).assists),
+        column(name("score"), move |player: &Player| player.team_stats(team).score),
+        column(name("time_building"), move |player: &Player| player.team_stats(team).time_building),
+        column(name("hits"), move |player: &Player| player.team_stats(team).hits),
+        column(name("onos_hits"), move |player: &Player| player.team_stats(team).onos_hits),
+        column(name("misses"), move |player: &Player| player.team_stats(team).misses),
+        column(name("killstreak"), move |player: &Player| player.team_stats(team).killstreak),
+        column(name("time_played"), move |player: &Player| player.team_stats(team).time_played),
+        column(name("commander_time"), move |player: &Player| player.team_stats(team).commander_time),
+        column(name("player_damage"), move |player: &Player| player.team_stats(team).player_damage),
+        column(name("structure_damage"), move |player: &Player| player.team_stats(team).structure_damage),
+    ]
+}
+
+/// The players of all rounds, sorted by round and Steam ID.
+fn all_players<'a>(games: &[&'a (GameId, GameStats)]) -> Vec<Player<'a>> {
+    let mut players = Vec::new();
+    for (id, game) in games {
+        let round = Round { id, game };
+        let mut round_players = game
+            .player_stats
+            .iter()
+            .map(|(&steam_id, stats)| Player { round, steam_id, stats })
+            .collect::<Vec<_>>();
+        round_players.sort_by_key(|player| player.steam_id);
+        players.extend(round_players);
+    }
+    players
+}
+
+fn players<'a>(games: &[&'a (GameId, GameStats)]) -> Table<'a, Player<'a>> {
+    let mut columns = vec![
+        game_id(|player: &Player<'a>| player.round),
+        column("steam_id", |player: &Player| player.steam_id),
+        column("player_name", |player: &Player| player.stats.player_name.clone()),
+        column("team", |player: &Player| team_name(player.team())),
+        column("won", |player: &Player| {
+            let winner = match player.team() {
+                Team::Marines => WinningTeam::Marines,
+                Team::Aliens => WinningTeam::Aliens,
+            };
+            player.round.game.round_info.winning_team == winner
+        }),
+        column("is_rookie", |player: &Player| player.stats.is_rookie),
+        column("last_team", |player: &Player| team_name(player.stats.last_team)),
+        column("hive_skill", |player: &Player| player.stats.hive_skill),
+        column("commander_skill", |player: &Player| player.stats.commander_skill),
+        column("commander_skill_offset", |player: &Player| player.stats.commander_skill_offset),
+        column("player_skill_offset", |player: &Player| player.stats.player_skill_offset),
+    ];
+    columns.extend(team_stats(Team::Marines));
+    columns.extend(team_stats(Team::Aliens));
+    Table {
+        name: "players",
+        columns,
+        rows: all_players(games),
+    }
+}
+
+fn weapons<'a>(games: &[&'a (GameId, GameStats)]) -> Table<'a, PlayerWeapon<'a>> {
+    let columns = vec![
+        game_id(|weapon: &PlayerWeapon<'a>| weapon.player.round),
+        column("steam_id", |weapon: &PlayerWeapon| weapon.player.steam_id),
+        column("player_name", |weapon: &PlayerWeapon| weapon.player.stats.player_name.clone()),
+        column("weapon", |weapon: &PlayerWeapon| weapon.name),
+        column("team", |weapon: &PlayerWeapon| team_name(weapon.weapon.team)),
+        column("kills", |weapon: &PlayerWeapon| weapon.weapon.kills),
+        column("hits", |weapon: &PlayerWeapon| weapon.weapon.hits),
+        column("onos_hits", |weapon: &PlayerWeapon| weapon.weapon.onos_hits),
+        column("misses", |weapon: &PlayerWeapon| weapon.weapon.misses),
+        column("player_damage", |weapon: &PlayerWeapon| weapon.weapon.player_damage),
+        column("structure_damage", |weapon: &PlayerWeapon| weapon.weapon.structure_damage),
+    ];
+    let mut rows = Vec::new();
+    for player in all_players(games) {
+        let mut weapons = player.stats.weapons.iter().collect::<Vec<_>>();
+        weapons.sort_by_key(|(name, _)| *name);
+        rows.extend(weapons.into_iter().map(|(name, weapon)| PlayerWeapon { player, name, weapon }));
+    }
+    Table {
+        name: "weapons",
+        columns,
+        rows,
+    }
+}
+
+fn events<'a, E>(games: &[&'a (GameId, GameStats)], events: impl Fn(&'a GameStats) -> &'a [E]) -> Vec<RoundEvent<'a, E>> {
+    games
+        .iter()
+        .flat_map(|(id, game)| {
+            events(game).iter().map(move |event| RoundEvent {
+                round: Round { id, game },
+                event,
+            })
+        })
+        .collect()
+}
+
+fn kills<'a>(games: &[&'a (GameId, GameStats)]) -> Table<'a, RoundEvent<'a, KillFeed>> {
+    type Kill<'a> = RoundEvent<'a, KillFeed>;
+    let mut columns = vec![
+        game_id(|kill: &Kill<'a>| kill.round),
+        column("game_time", |kill: &Kill| kill.event.game_time),
+        column("killer_team", |kill: &Kill| team_name(kill.event.killer_team)),
+        column("killer_steam_id", |kill: &Kill| kill.event.killer_steam_id),
+        column("killer_name", |kill: &Kill| {
+            kill.event.killer_steam_id.and_then(|steam_id| kill.round.player_name(steam_id))
+        }),
+        column("killer_weapon", |kill: &Kill| kill.event.killer_weapon.clone()),
+        column("killer_class", |kill: &Kill| kill.event.killer_class.map(|class| format!("{:?}", class))),
+        column("killer_location", |kill: &Kill| kill.round.location_name(kill.event.killer_location)),
+    ];
+    columns.extend(position("killer_", |kill: &Kill| kill.event.killer_position.as_ref()));
+    columns.push(column("doer_location", |kill: &Kill| kill.round.location_name(kill.event.doer_location)));
+    columns.extend(position("doer_", |kill: &Kill| kill.event.doer_position.as_ref()));
+    columns.extend([
+        column("victim_steam_id", |kill: &Kill| kill.event.victim_steam_id),
+        column("victim_name", |kill: &Kill| kill.round.player_name(kill.event.victim_steam_id)),
+        column("victim_class", |kill: &Kill| format!("{:?}", kill.event.victim_class)),
+        column("victim_location", |kill: &Kill| kill.round.location_name(kill.event.victim_location)),
+    ]);
+    columns.extend(position("victim_", |kill: &Kill| Some(&kill.event.victim_position)));
+    Table {
+        name: "kills",
+        columns,
+        rows: events(games, |game| &game.kill_feed),
+    }
+}
+
+fn buildings<'a>(games: &[&'a (GameId, GameStats)]) -> Table<'a, RoundEvent<'a, Building>> {
+    type BuildingEvent<'a> = RoundEvent<'a, Building>;
+    let mut columns = vec![
+        game_id(|building: &BuildingEvent<'a>| building.round),
+        column("game_time", |building: &BuildingEvent| building.event.game_time),
+        column("team", |building: &BuildingEvent| team_name(building.event.team)),
+        column("tech_id", |building: &BuildingEvent| building.event.tech_id.clone()),
+        column("event", |building: &BuildingEvent| {
+            building.event.event.as_ref().map(|event| format!("{:?}", event))
+        }),
+        column("built", |building: &BuildingEvent| building.event.built),
+        column("recycled", |building: &BuildingEvent| building.event.recycled),
+        column("destroyed", |building: &BuildingEvent| building.event.destroyed),
+        column("biomass", |building: &BuildingEvent| building.event.biomass),
+        column("entity_id", |building: &BuildingEvent| building.event.entity_id),
+    ];
+    columns.extend(position("", |building: &BuildingEvent| Some(&building.event.location)));
+    Table {
+        name: "buildings",
+        columns,
+        rows: events(games, |game| &game.buildings),
+    }
+}
+
+fn research<'a>(games: &[&'a (GameId, GameStats)]) -> Table<'a, RoundEvent<'a, Research>> {
+    type ResearchEvent<'a> = RoundEvent<'a, Research>;
+    let columns = vec![
+        game_id(|research: &ResearchEvent<'a>| research.round),
+        column("game_time", |research: &ResearchEvent| research.event.game_time),
+        column("team", |research: &ResearchEvent| team_name(research.event.team)),
+        column("research_id", |research: &ResearchEvent| research.event.research_id.clone()),
+    ];
+    Table {
+        name: "research",
+        columns,
+        rows: events(games, |game| &game.research),
+    }
+}
+
+fn write_csv<T>(table: &Table<T>, path: &Path) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let header = table.columns.iter().map(|column| csv_field(&column.name)).collect::<Vec<_>>();
+    writeln!(out, "{}", header.join(","))?;
+    for row in &table.rows {
+        let fields = table.columns.iter().map(|column| match (column.get)(row) {
+            Cell::Integer(n) => n.to_string(),
+            Cell::Float(n) => n.to_string(),
+            Cell::Boolean(b) => b.to_string(),
+            Cell::Text(text) => csv_field(&text),
+            Cell::Null => String::new(),
+        });
+        writeln!(out, "{}", fields.collect::<Vec<_>>().join(","))?;
+    }
+    out.flush()
+}
+
+#[cfg(feature = "parquet")]
+fn write_parquet<T>(table: &Table<T>, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    use std::sync::Arc;
+
+    use arrow_array::{ArrayRef, BooleanArray, Float32Array, Int64Array, RecordBatch, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use parquet::arrow::ArrowWriter;
+    use parquet::basic::Compression;
+    use parquet::file::properties::WriterProperties;
+
+    let fields = table.columns.iter().map(|column| {
+        let data_type = match column.kind {
+            Kind::Integer => DataType::Int64,
+            Kind::Float => DataType::Float32,
+            Kind::Boolean => DataType::Boolean,
+            Kind::Text => DataType::Utf8,
+        };
+        Field::new(&column.name, data_type, true)
+    });
+    let schema = Arc::new(Schema::new(fields.collect::<Vec<_>>()));
+    let cells = |column: &Column<T>| table.rows.iter().map(|row| (column.get)(row)).collect::<Vec<_>>();
+    let arrays = table.columns.iter().map(|column| -> ArrayRef {
+        // the cells of a column are always of its kind or null
+        match column.kind {
+            Kind::Integer => Arc::new(Int64Array::from_iter(cells(column).into_iter().map(|cell| match cell {
+                Cell::Integer(n) => Some(n),
+                _ => None,
+            }))),
+            Kind::Float => Arc::new(Float32Array::from_iter(cells(column).into_iter().map(|cell| match cell {
+                Cell::Float(n) => Some(n),
+                _ => None,
+            }))),
+            Kind::Boolean => Arc::new(BooleanArray::from_iter(cells(column).into_iter().map(|cell| match cell {
+                Cell::Boolean(b) => Some(b),
+                _ => None,
+            }))),
+            Kind::Text => Arc::new(StringArray::from_iter(cells(column).into_iter().map(|cell| match cell {
+                Cell::Text(text) => Some(text),
+                _ => None,
+            }))),
+        }
+    });
+    let batch = RecordBatch::try_new(schema.clone(), arrays.collect())?;
+    let properties = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+    let mut writer = ArrowWriter::try_new(File::create(path)?, schema, Some(properties))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+fn write<T>(table: Table<T>, directory: &Path, format: FileFormat, rows: &mut Vec<[Value; 3]>) -> Result<(), String> {
+    let path = directory.join(format!("{}.{}", table.name, format.extension()));
+    let result = match format {
+        FileFormat::Csv => write_csv(&table, &path).map_err(|e| e.to_string()),
+        #[cfg(feature = "parquet")]
+        FileFormat::Parquet => write_parquet(&table, &path).map_err(|e| e.to_string()),
+    };
+    result.map_err(|e| format!("failed to write `{}`\n{}", path.display(), e))?;
+    rows.push(row![table.name, path.display().to_string(), table.rows.len()]);
+    Ok(())
+}
+
+/// Writes the games as tables into `directory`: `rounds`, `players` with a row per player per round, `weapons` with a
+/// row per weapon per player per round, and `kills`, `buildings` and `research` with a row per event. All tables are
+/// joined by the `game_id` column.
+pub fn export(games: &[&(GameId, GameStats)], directory: &Path, format: FileFormat) -> Result<Report, String> {
+    fs::create_dir_all(directory).map_err(|e| format!("failed to create `{}`\n{}", directory.display(), e))?;
+    let mut rows = Vec::new();
+    write(rounds(games), directory, format, &mut rows)?;
+    write(players(games), directory, format, &mut rows)?;
+    write(weapons(games), directory, format, &mut rows)?;
+    write(kills(games), directory, format, &mut rows)?;
+    write(buildings(games), directory, format, &mut rows)?;
+    write(research(games), directory, format, &mut rows)?;
+
+    let mut report = Report::new();
+    report.table("Tables", ["TABLE", "FILE", "ROWS"], [Alignment::Left, Alignment::Left, Alignment::Right], rows);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use ns2_stat::testing::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn export_tidy_tables() {
+        let games = crate::load_data("../test_data", &Default::default()).unwrap();
+        let games = games.iter().take(3).collect::<Vec<_>>();
+        let directory = TempDir::new("tables");
+        export(&games, &directory, FileFormat::Csv).unwrap();
+
+        let players = fs::read_to_string(directory.join("players.csv")).unwrap();
+        let mut lines = players.lines();
+        assert!(lines.next().unwrap().starts_with("game_id,steam_id,player_name,team,won,"));
+        let rows = games.iter().map(|(_, game)| game.player_stats.len()).sum::<usize>();
+        assert_eq!(lines.count(), rows);
+        let kills = fs::read_to_string(directory.join("kills.csv")).unwrap();
+        assert_eq!(kills.lines().count(), 1 + games.iter().map(|(_, game)| game.kill_feed.len()).sum::<usize>());
+
+        #[cfg(feature = "parquet")]
+        {
+            use parquet::file::reader::{FileReader, SerializedFileReader};
+
+            export(&games, &directory, FileFormat::Parquet).unwrap();
+            let reader = SerializedFileReader::new(File::open(directory.join("players.parquet")).unwrap()).unwrap();
+            assert_eq!(reader.metadata().file_metadata().num_rows(), rows as i64);
+        }
+    }
+}
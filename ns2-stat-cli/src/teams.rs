@@ -1,38 +1,321 @@
-use ns2_stat::GameSummary;
+use clap::Args;
+use ns2_stat::prediction::{Roster, WinModel};
+use ns2_stat::{GameSummary, NS2Stats, Stat, TeamSummary};
 
 use crate::helpers;
+use crate::row;
+use crate::table::{Alignment, Report, Value};
+
+pub use balanced_partitioning::{Constraints, RecentTeams};
+
+#[derive(Args)]
+pub struct TeamsArgs {
+    /// The players to split into teams
+    #[clap(required = true)]
+    pub players: Vec<String>,
+    #[clap(long)]
+    pub marine_com: Option<String>,
+    #[clap(long)]
+    pub alien_com: Option<String>,
+    /// Players that have to be in the same team, separated by commas
+    #[clap(long)]
+    pub together: Vec<String>,
+    /// Players that must not all be in the same team, separated by commas
+    #[clap(long)]
+    pub apart: Vec<String>,
+    /// Players that would rather play marines, separated by commas
+    #[clap(long, value_delimiter = ',')]
+    pub prefer_marines: Vec<String>,
+    /// Players that would rather play aliens, separated by commas
+    #[clap(long, value_delimiter = ',')]
+    pub prefer_aliens: Vec<String>,
+    /// The number of team suggestions
+    #[clap(long, default_value = "4")]
+    pub suggestions: usize,
+    /// The number of recent games whose teams should not be repeated
+    #[clap(long, default_value = "1")]
+    pub recent: usize,
+    /// How much repeating recent teams is avoided compared to balancing the teams
+    #[clap(long, default_value = "1.0")]
+    pub repeat_weight: f32,
+}
+
+impl TeamsArgs {
+    pub fn constraints(&self) -> Constraints {
+        let groups = |groups: &[String]| groups.iter().map(|group| group.split(',').map(|player| player.to_owned()).collect()).collect();
+        Constraints {
+            marine_commander: self.marine_com.clone(),
+            alien_commander: self.alien_com.clone(),
+            together: groups(&self.together),
+            apart: groups(&self.apart),
+            prefer_marines: self.prefer_marines.clone(),
+            prefer_aliens: self.prefer_aliens.clone(),
+            recent_teams: Vec::new(),
+            repeat_weight: self.repeat_weight,
+        }
+    }
+}
 
-#[allow(dead_code)]
 mod balanced_partitioning {
+    use std::collections::HashMap;
+
     use ns2_stat::Stat;
 
-    /// Suggests teams by solving the [balanced partitioning problem](https://en.wikipedia.org/wiki/Balanced_number_partitioning).
-    /// The first team is marines and the second is aliens.
-    pub fn balanced_partitioning<S: AsRef<str>>(players: &[S], score: impl Fn(&str) -> Stat<f32>) -> impl Iterator<Item = (Vec<&str>, Vec<&str>)> {
-        // Compute the sums of all possible partitions in an array with 2^n elements.
-        // Each possibility is encoded as a bit pattern (the index of the respective sum),
-        // where a 0 indicates the 1st team and a 1 indicates the 2nd team.
-        let n = 1 << players.len();
-        let mut total_scores: Vec<(usize, f32)> = (0..n).map(|p| (p, 0.0)).collect();
-        for (i, player) in players.iter().enumerate() {
-            let stat = score(player.as_ref());
-            for p in 0..n {
+    /// Up to this many players, all possible partitions are checked.
+    const EXHAUSTIVE_LIMIT: usize = 16;
+    /// The number of starting points for the local search.
+    const STARTS: usize = 32;
+    /// A violated side preference costs as much as this fraction of the average player score.
+    const PREFERENCE_PENALTY: f32 = 0.5;
+
+    /// Restrictions for the suggested teams.
+    #[derive(Default)]
+    pub struct Constraints {
+        pub marine_commander: Option<String>,
+        pub alien_commander: Option<String>,
+        /// Groups of players that have to be in the same team.
+        pub together: Vec<Vec<String>>,
+        /// Groups of players that must not all be in the same team.
+        pub apart: Vec<Vec<String>>,
+        /// Players that would rather play marines. This is not guaranteed.
+        pub prefer_marines: Vec<String>,
+        /// Players that would rather play aliens. This is not guaranteed.
+        pub prefer_aliens: Vec<String>,
+        /// The teams of the recent games, the most recent first. Repeating their sides and teammates is penalized.
+        pub recent_teams: Vec<RecentTeams>,
+        /// With a weight of 1, repeating the teams of the most recent game costs as much as the score of an average player.
+        pub repeat_weight: f32,
+    }
+
+    pub struct RecentTeams {
+        pub marines: Vec<String>,
+        pub aliens: Vec<String>,
+    }
+
+    pub struct Partition<'a> {
+        pub marines: Vec<&'a str>,
+        pub aliens: Vec<&'a str>,
+        /// The summed marine scores of the marines.
+        pub marine_score: f32,
+        /// The summed alien scores of the aliens.
+        pub alien_score: f32,
+        /// The number of violated side preferences.
+        pub violated_preferences: u32,
+        /// How much the recent teams are repeated. Repeating the most recent game exactly adds 1, older games count less.
+        pub repetition: f32,
+    }
+
+    /// A recent game, resolved to player indices.
+    struct RecentGame {
+        /// The players that were marines.
+        marines: u64,
+        /// The players that were aliens.
+        aliens: u64,
+        weight: f32,
+    }
+
+    /// The partition problem with the constraints resolved to player indices.
+    /// Each partition is encoded as a bit pattern, where a 0 indicates the 1st team (marines) and a 1 indicates the 2nd team (aliens).
+    struct Problem {
+        scores: Vec<Stat<f32>>,
+        /// Bits that have to be set to 0.
+        marines: u64,
+        /// Bits that have to be set to 1.
+        aliens: u64,
+        together: Vec<u64>,
+        apart: Vec<u64>,
+        prefer_marines: u64,
+        prefer_aliens: u64,
+        preference_penalty: f32,
+        recent_games: Vec<RecentGame>,
+        repeat_penalty: f32,
+    }
+
+    impl Problem {
+        /// Returns the number of violated hard constraints and the cost, which should be compared lexicographically.
+        fn cost(&self, p: u64) -> (u32, f32) {
+            let n = self.scores.len();
+            let mut violations = (usize::abs_diff(n, 2 * p.count_ones() as usize) > 1) as u32; // the player difference between two teams has to be <= 1
+            violations += (p & self.marines).count_ones() + (!p & self.aliens).count_ones();
+            violations += self.together.iter().filter(|&&group| p & group != 0 && p & group != group).count() as u32;
+            violations += self.apart.iter().filter(|&&group| p & group == 0 || p & group == group).count() as u32;
+            let (marine_score, alien_score) = self.team_scores(p);
+            let penalty = self.preference_penalty * self.violated_preferences(p) as f32 + self.repeat_penalty * self.repetition(p);
+            (violations, (marine_score - alien_score).abs() + penalty)
+        }
+
+        fn team_scores(&self, p: u64) -> (f32, f32) {
+            let mut marine_score = 0.0;
+            let mut alien_score = 0.0;
+            for (i, score) in self.scores.iter().enumerate() {
                 if (p >> i) & 1 == 0 {
-                    // marines
-                    total_scores[p].1 += stat.marines;
+                    marine_score += score.marines;
+                } else {
+                    alien_score += score.aliens;
+                }
+            }
+            (marine_score, alien_score)
+        }
+
+        fn violated_preferences(&self, p: u64) -> u32 {
+            (p & self.prefer_marines).count_ones() + (!p & self.prefer_aliens).count_ones()
+        }
+
+        /// For every player of a recent game, a repeated side and the fraction of repeated teammates each count half.
+        /// The result is averaged over the players and summed over the weighted games.
+        fn repetition(&self, p: u64) -> f32 {
+            let all = (1 << self.scores.len()) - 1;
+            let (marines, aliens) = (!p & all, p);
+            self.recent_games
+                .iter()
+                .map(|game| {
+                    let players = game.marines | game.aliens;
+                    if players == 0 {
+                        return 0.0;
+                    }
+                    let mut repetition = ((marines & game.marines).count_ones() + (aliens & game.aliens).count_ones()) as f32;
+                    for i in (0..self.scores.len()).filter(|i| (players >> i) & 1 == 1) {
+                        let team = if (p >> i) & 1 == 0 { marines } else { aliens } & !(1 << i);
+                        let recent_team = if (game.marines >> i) & 1 == 1 { game.marines } else { game.aliens } & !(1 << i);
+                        if team != 0 {
+                            repetition += (team & recent_team).count_ones() as f32 / team.count_ones() as f32;
+                        }
+                    }
+                    game.weight * repetition / (2 * players.count_ones()) as f32
+                })
+                .sum()
+        }
+
+        /// Evaluates all valid partitions.
+        fn exhaustive(&self) -> HashMap<u64, f32> {
+            (0..1 << self.scores.len())
+                .filter_map(|p| match self.cost(p) {
+                    (0, cost) => Some((p, cost)),
+                    _ => None,
+                })
+                .collect()
+        }
+
+        /// Evaluates the partitions visited by a local search that moves single players or swaps two players,
+        /// starting from the greedy partition and pseudo-random partitions.
+        fn local_search(&self) -> HashMap<u64, f32> {
+            let n = self.scores.len();
+            let mut visited = HashMap::new();
+            let mut rng = 0x9e37_79b9_7f4a_7c15_u64; // fixed seed, so the suggestions are reproducible
+            let mut starts = vec![self.greedy()];
+            starts.extend((1..STARTS).map(|_| {
+                // xorshift
+                rng ^= rng << 13;
+                rng ^= rng >> 7;
+                rng ^= rng << 17;
+                rng & ((1 << n) - 1)
+            }));
+
+            for mut p in starts {
+                let mut cost = self.cost(p);
+                loop {
+                    let mut best = (p, cost);
+                    for i in 0..n {
+                        for j in i..n {
+                            if i != j && (p >> i) & 1 == (p >> j) & 1 {
+                                continue;
+                            }
+                            let q = p ^ (1 << i) ^ if i == j { 0 } else { 1 << j };
+                            let q_cost = self.cost(q);
+                            if q_cost.0 == 0 {
+                                visited.insert(q, q_cost.1);
+                            }
+                            if q_cost.0 < best.1 .0 || (q_cost.0 == best.1 .0 && q_cost.1 < best.1 .1) {
+                                best = (q, q_cost);
+                            }
+                        }
+                    }
+                    if best.0 == p {
+                        break;
+                    }
+                    (p, cost) = best;
+                }
+            }
+            visited
+        }
+
+        /// Assigns the players by descending score to the team with the lower total score.
+        fn greedy(&self) -> u64 {
+            let mut order = (0..self.scores.len()).collect::<Vec<_>>();
+            order.sort_by(|&i, &j| f32::total_cmp(&self.scores[i].total, &self.scores[j].total).reverse());
+            let (mut p, mut marine_score, mut alien_score) = (0, 0.0, 0.0);
+            for i in order {
+                if marine_score <= alien_score {
+                    marine_score += self.scores[i].marines;
                 } else {
-                    // aliens
-                    total_scores[p].1 -= stat.aliens;
+                    alien_score += self.scores[i].aliens;
+                    p |= 1 << i;
                 }
             }
+            p
         }
+    }
+
+    /// Suggests teams by solving the [balanced partitioning problem](https://en.wikipedia.org/wiki/Balanced_number_partitioning).
+    /// Returns the `count` best partitions that satisfy the constraints, the best partition first.
+    ///
+    /// For more than 16 players, a local search is used instead of checking all partitions,
+    /// so the result is not guaranteed to be optimal.
+    pub fn balanced_partitioning<'a, S: AsRef<str>>(
+        players: &'a [S],
+        score: impl Fn(&str) -> Stat<f32>,
+        constraints: &Constraints,
+        count: usize,
+    ) -> Vec<Partition<'a>> {
+        assert!(players.len() < 64, "at most 63 players are supported");
+        let mask = |names: &[String]| {
+            players
+                .iter()
+                .enumerate()
+                .filter(|(_, player)| names.iter().any(|name| name == player.as_ref()))
+                .fold(0, |mask, (i, _)| mask | 1 << i)
+        };
+        let scores = players.iter().map(|player| score(player.as_ref())).collect::<Vec<_>>();
+        let average_score = scores.iter().map(|score| score.total).sum::<f32>() / scores.len() as f32;
+        let problem = Problem {
+            marines: mask(constraints.marine_commander.as_slice()),
+            aliens: mask(constraints.alien_commander.as_slice()),
+            together: constraints.together.iter().map(|group| mask(group)).collect(),
+            // a single player can't be all of a team, so such groups are always satisfied
+            apart: constraints
+                .apart
+                .iter()
+                .map(|group| mask(group))
+                .filter(|group: &u64| group.count_ones() > 1)
+                .collect(),
+            prefer_marines: mask(&constraints.prefer_marines),
+            prefer_aliens: mask(&constraints.prefer_aliens),
+            preference_penalty: PREFERENCE_PENALTY * average_score,
+            recent_games: constraints
+                .recent_teams
+                .iter()
+                .enumerate()
+                .map(|(k, teams)| RecentGame {
+                    marines: mask(&teams.marines),
+                    aliens: mask(&teams.aliens),
+                    weight: 1.0 / (k + 1) as f32,
+                })
+                .collect(),
+            repeat_penalty: constraints.repeat_weight * average_score,
+            scores,
+        };
 
-        total_scores.sort_by(|(_, score1), (_, score2)| f32::total_cmp(&score1.abs(), &score2.abs()));
-        total_scores
+        let partitions = if players.len() <= EXHAUSTIVE_LIMIT {
+            problem.exhaustive()
+        } else {
+            problem.local_search()
+        };
+        let mut partitions = partitions.into_iter().collect::<Vec<_>>();
+        partitions.sort_by(|(_, cost1), (_, cost2)| f32::total_cmp(cost1, cost2));
+        partitions
             .into_iter()
-            .map(|(p, _)| p)
-            .filter(|p| usize::abs_diff(players.len(), 2 * p.count_ones() as usize) <= 1) // the player difference between two teams has to be <= 1
-            .map(|p| {
+            .take(count)
+            .map(|(p, _)| {
                 let mut marines = Vec::with_capacity(players.len() / 2);
                 let mut aliens = Vec::with_capacity(players.len() / 2);
                 for (i, player) in players.iter().enumerate() {
@@ -42,51 +325,336 @@ mod balanced_partitioning {
                         aliens.push(player.as_ref());
                     }
                 }
-                (marines, aliens)
+                let (marine_score, alien_score) = problem.team_scores(p);
+                Partition {
+                    marines,
+                    aliens,
+                    marine_score,
+                    alien_score,
+                    violated_preferences: problem.violated_preferences(p),
+                    repetition: problem.repetition(p),
+                }
             })
+            .collect()
     }
 }
 
 /// Analyzes the past games, sorted by the length, in descending order.
-fn analyze_past_games(
-    mut games: Vec<GameSummary>,
-    players: Vec<String>,
-    marine_commander: Option<String>,
-    alien_commander: Option<String>,
-) -> impl Iterator<Item = GameSummary> {
+fn analyze_past_games<'a>(mut games: Vec<GameSummary>, players: &'a [String], constraints: &'a Constraints) -> impl Iterator<Item = GameSummary> + 'a {
     // sort by length in descending order
     games.sort_by(|game1, game2| f32::total_cmp(&game1.round_length, &game2.round_length).reverse());
 
     games.into_iter().filter(move |game| {
         players.len() == game.marines.players.len() + game.aliens.players.len() // correct amount of players
-            && marine_commander.as_ref() == game.marines.commander.as_ref() // marine commander matches
-            && alien_commander.as_ref() == game.aliens.commander.as_ref() // alien commander matches
+            && constraints.marine_commander.as_ref() == game.marines.commander.as_ref() // marine commander matches
+            && constraints.alien_commander.as_ref() == game.aliens.commander.as_ref() // alien commander matches
             && players.iter().all(|player| game.marines.players.contains_key(player) || game.aliens.players.contains_key(player)) // all players match
     })
 }
 
-/// Print balanced team suggestions.
-pub fn suggest_teams(games: Vec<GameSummary>, players: Vec<String>, marine_commander: Option<String>, alien_commander: Option<String>) {
-    println!("Team suggestions");
-    println!("================");
-    analyze_past_games(games, players, marine_commander, alien_commander).take(4).for_each(|game| {
-        println!();
-        println!(
-            "Marines: {}",
-            helpers::format_with(game.marines.players.keys(), ", ", |f, player| if game.marines.is_commander(&player) {
-                write!(f, "[{}]", player)
-            } else {
-                write!(f, "{}", player)
-            }),
-        );
-        println!(
-            "Aliens: {}",
-            helpers::format_with(game.aliens.players.keys(), ", ", |f, player| if game.aliens.is_commander(&player) {
-                write!(f, "[{}]", player)
+fn roster(marines: &TeamSummary, aliens: &TeamSummary) -> Roster {
+    Roster {
+        marines: marines.players.keys().cloned().collect(),
+        aliens: aliens.players.keys().cloned().collect(),
+        marine_commander: marines.commander.clone(),
+        alien_commander: aliens.commander.clone(),
+        map_name: None,
+    }
+}
+
+/// The score of players without games if no player has games.
+const DEFAULT_SCORE: f32 = 1.0;
+
+/// The average score of the players with games.
+fn average_score(stats: &NS2Stats) -> f32 {
+    let known = stats
+        .users
+        .values()
+        .filter(|user| user.games.total > 0)
+        .map(|user| user.average_score().total)
+        .collect::<Vec<_>>();
+    if known.is_empty() {
+        DEFAULT_SCORE
+    } else {
+        known.iter().sum::<f32>() / known.len() as f32
+    }
+}
+
+/// The average score of a player per side. Sides without games fall back to the total,
+/// unknown players to the average of the known players.
+fn player_score(stats: &NS2Stats, player: &str, average: f32) -> Stat<f32> {
+    match stats.users.get(player) {
+        Some(user) if user.games.total > 0 => {
+            let score = user.average_score();
+            let or_total = |side: f32| if side.is_nan() { score.total } else { side };
+            Stat {
+                total: score.total,
+                marines: or_total(score.marines),
+                aliens: or_total(score.aliens),
+            }
+        }
+        _ => Stat {
+            total: average,
+            marines: average,
+            aliens: average,
+        },
+    }
+}
+
+/// Describes how much the suggested teams differ from the teams of the previous game.
+fn describe_changes(marines: &[&str], aliens: &[&str], previous: &GameSummary) -> String {
+    let mut present = 0;
+    let mut switched = 0;
+    let mut kept_teammates = 0.0;
+    for (team, previous_team, previous_other_team) in [(marines, &previous.marines, &previous.aliens), (aliens, &previous.aliens, &previous.marines)] {
+        for &player in team {
+            let previous_teammates = if previous_team.players.contains_key(player) {
+                previous_team
+            } else if previous_other_team.players.contains_key(player) {
+                switched += 1;
+                previous_other_team
             } else {
-                write!(f, "{}", player)
-            }),
-        );
-        println!("({:.3} min, winner: {:?})", game.round_length / 60.0, game.winning_team);
-    });
+                continue;
+            };
+            present += 1;
+            let teammates = team.iter().filter(|&&teammate| teammate != player);
+            let kept = teammates.clone().filter(|&&teammate| previous_teammates.players.contains_key(teammate)).count();
+            kept_teammates += kept as f32 / teammates.count().max(1) as f32;
+        }
+    }
+    if present == 0 {
+        return "no players of the previous round".to_owned();
+    }
+    format!(
+        "{} of {} players switch sides, {:.0}% of teammates stay the same",
+        switched,
+        present,
+        kept_teammates * 100.0 / present as f32
+    )
+}
+
+fn format_team(players: &[&str], commander: Option<&str>) -> String {
+    helpers::format_with(players.iter(), ", ", |f, &player| {
+        if commander == Some(player) {
+            write!(f, "[{}]", player)
+        } else {
+            write!(f, "{}", player)
+        }
+    })
+    .to_string()
+}
+
+/// Balanced team suggestions. The `recent_games` latest games are used to avoid repeating teams.
+pub fn suggest_teams(
+    model: &WinModel,
+    stats: &NS2Stats,
+    mut games: Vec<GameSummary>,
+    mut players: Vec<String>,
+    mut constraints: Constraints,
+    count: usize,
+    recent_games: usize,
+) -> Result<Report, String> {
+    games.sort_by_key(|game| std::cmp::Reverse(game.round_date));
+    constraints.recent_teams = games
+        .iter()
+        .take(recent_games)
+        .map(|game| RecentTeams {
+            marines: game.marines.players.keys().cloned().collect(),
+            aliens: game.aliens.players.keys().cloned().collect(),
+        })
+        .collect();
+    let previous = games.first().filter(|_| recent_games > 0);
+
+    for commander in [&constraints.marine_commander, &constraints.alien_commander].into_iter().flatten() {
+        if !players.contains(commander) {
+            players.push(commander.clone());
+        }
+    }
+    if players.len() >= 64 {
+        return Err(format!("at most 63 players are supported, got {}", players.len()));
+    }
+    let mut unknown = constraints
+        .together
+        .iter()
+        .chain(&constraints.apart)
+        .flatten()
+        .filter(|name| !players.contains(name))
+        .collect::<Vec<_>>();
+    unknown.sort_unstable();
+    unknown.dedup();
+    if !unknown.is_empty() {
+        return Err(format!(
+            "the constraints name players that are not in the player list: {}",
+            helpers::format_with(unknown.iter(), ", ", |f, name| write!(f, "{}", name))
+        ));
+    }
+
+    let average = average_score(stats);
+    let partitions = balanced_partitioning::balanced_partitioning(&players, |player| player_score(stats, player, average), &constraints, count);
+    if partitions.is_empty() {
+        return Err("no teams satisfy the constraints".to_owned());
+    }
+    let mut report = Report::new();
+    report.table(
+        "Team suggestions",
+        [
+            "MARINES",
+            "ALIENS",
+            "MARINE SCORE",
+            "ALIEN SCORE",
+            "PREDICTED MARINE WR",
+            "UNMET PREFERENCES",
+            "REPETITION",
+            "CHANGES",
+        ],
+        [
+            Alignment::Left,
+            Alignment::Left,
+            Alignment::Right,
+            Alignment::Right,
+            Alignment::Right,
+            Alignment::Right,
+            Alignment::Right,
+            Alignment::Left,
+        ],
+        partitions.into_iter().map(|partition| {
+            let prediction = model.predict(&Roster {
+                marines: partition.marines.iter().map(|&player| player.to_owned()).collect(),
+                aliens: partition.aliens.iter().map(|&player| player.to_owned()).collect(),
+                marine_commander: constraints.marine_commander.clone(),
+                alien_commander: constraints.alien_commander.clone(),
+                map_name: None,
+            });
+            row![
+                format_team(&partition.marines, constraints.marine_commander.as_deref()),
+                format_team(&partition.aliens, constraints.alien_commander.as_deref()),
+                Value::number(partition.marine_score, 3),
+                Value::number(partition.alien_score, 3),
+                Value::percent(prediction.marines * 100.0),
+                partition.violated_preferences,
+                partition.repetition,
+                previous.map(|previous| describe_changes(&partition.marines, &partition.aliens, previous))
+            ]
+        }),
+    );
+
+    let team = |team: &TeamSummary| {
+        format_team(
+            &team.players.keys().map(|player| player.as_str()).collect::<Vec<_>>(),
+            team.commander.as_deref(),
+        )
+    };
+    report.table(
+        "Past games",
+        ["MARINES", "ALIENS", "LENGTH", "WINNER", "PREDICTED MARINE WR"],
+        [Alignment::Left, Alignment::Left, Alignment::Right, Alignment::Left, Alignment::Right],
+        analyze_past_games(games, &players, &constraints).take(4).map(|game| {
+            let prediction = model.predict(&roster(&game.marines, &game.aliens));
+            row![
+                team(&game.marines),
+                team(&game.aliens),
+                helpers::format_duration(game.round_length),
+                format!("{:?}", game.winning_team),
+                Value::percent(prediction.marines * 100.0)
+            ]
+        }),
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::balanced_partitioning::*;
+    use super::*;
+
+    fn score(player: &str) -> Stat<f32> {
+        let score = player.len() as f32;
+        Stat {
+            total: score,
+            marines: score,
+            aliens: score,
+        }
+    }
+
+    #[test]
+    fn balanced_partitioning_constraints() {
+        let players = ["a", "bb", "ccc", "dddd", "eeeee", "ffffff"];
+        let constraints = Constraints {
+            marine_commander: Some("ffffff".to_owned()),
+            together: vec![vec!["a".to_owned(), "bb".to_owned()]],
+            apart: vec![vec!["ccc".to_owned(), "dddd".to_owned()]],
+            ..Default::default()
+        };
+        let partitions = balanced_partitioning(&players, score, &constraints, 10);
+        assert!(!partitions.is_empty());
+        for partition in &partitions {
+            assert!(partition.marines.contains(&"ffffff"));
+            assert_eq!(partition.marines.contains(&"a"), partition.marines.contains(&"bb"));
+            assert_ne!(partition.marines.contains(&"ccc"), partition.marines.contains(&"dddd"));
+        }
+        assert_eq!(partitions.len(), 2);
+        assert_eq!(partitions[0].marines, ["ccc", "eeeee", "ffffff"]);
+    }
+
+    #[test]
+    fn balanced_partitioning_local_search() {
+        let players = (1..=24).map(|i| "x".repeat(i)).collect::<Vec<_>>();
+        let partitions = balanced_partitioning(&players, score, &Constraints::default(), 4);
+        assert_eq!(partitions.len(), 4);
+        assert_eq!(partitions[0].marines.len(), 12);
+        assert_eq!(partitions[0].marine_score, partitions[0].alien_score);
+    }
+
+    #[test]
+    fn balanced_partitioning_avoids_recent_teams() {
+        let players = ["a", "b", "c", "d"];
+        let constraints = Constraints {
+            recent_teams: vec![RecentTeams {
+                marines: vec!["a".to_owned(), "b".to_owned()],
+                aliens: vec!["c".to_owned(), "d".to_owned()],
+            }],
+            repeat_weight: 1.0,
+            ..Default::default()
+        };
+        let partitions = balanced_partitioning(&players, score, &constraints, 6);
+        // all players have the same score, so the least repetitive teams are the best:
+        // half of the players keep their side, but nobody keeps a teammate
+        assert_eq!(partitions[0].repetition, 0.25);
+        assert_eq!(partitions.last().map(|partition| partition.repetition), Some(1.0));
+    }
+
+    #[test]
+    fn single_player_apart_group() {
+        let players = ["a", "bb", "ccc", "dddd"];
+        let constraints = Constraints {
+            apart: vec![vec!["a".to_owned()]],
+            ..Default::default()
+        };
+        let partitions = balanced_partitioning(&players, score, &constraints, 1);
+        assert_eq!(partitions.len(), 1);
+        assert_eq!(partitions[0].marine_score, partitions[0].alien_score);
+    }
+
+    #[test]
+    fn suggest_teams_rejects_invalid_players() {
+        let stats = NS2Stats::default();
+        let model = WinModel::fit(std::iter::empty());
+        let players = (0..64).map(|i| i.to_string()).collect::<Vec<_>>();
+        assert!(suggest_teams(&model, &stats, Vec::new(), players, Constraints::default(), 1, 0).is_err());
+        let constraints = Constraints {
+            together: vec![vec!["a".to_owned(), "x".to_owned()]],
+            ..Default::default()
+        };
+        let error = suggest_teams(&model, &stats, Vec::new(), vec!["a".to_owned(), "b".to_owned()], constraints, 1, 0).err();
+        assert_eq!(error.as_deref(), Some("the constraints name players that are not in the player list: x"));
+    }
+
+    #[test]
+    fn suggest_teams_without_known_players() {
+        let stats = NS2Stats::default();
+        assert_eq!(average_score(&stats), DEFAULT_SCORE);
+        let model = WinModel::fit(std::iter::empty());
+        let players = ["a", "b", "c", "d"].map(str::to_owned).to_vec();
+        assert!(suggest_teams(&model, &stats, Vec::new(), players, Constraints::default(), 1, 0).is_ok());
+    }
 }
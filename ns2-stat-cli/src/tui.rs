@@ -0,0 +1,583 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+use std::time::Duration;
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::ExecutableCommand;
+use ns2_stat::hive_skill::HiveSkillHistory;
+use ns2_stat::input_types::{GameStats, Team};
+use ns2_stat::player::PlayerProfile;
+use ns2_stat::{summarize_game, GameId, GameSummary, NS2Stats, TeamSummary};
+use ratatui::prelude::*;
+use ratatui::widgets::{Axis, Block, Borders, Cell, Chart, Dataset, GraphType, Paragraph, Row, Table, TableState, Tabs};
+
+use crate::helpers;
+use crate::stats::{self, Metric, Side};
+
+/// The columns of the player table, after the name.
+const PLAYER_COLUMNS: [Metric; 7] = [
+    Metric::Games,
+    Metric::WinRate,
+    Metric::Kd,
+    Metric::Kda,
+    Metric::AvgScore,
+    Metric::Accuracy,
+    Metric::HiveSkill,
+];
+
+/// The date ranges that can be cycled through, in days before the latest game.
+const PERIODS: [Option<u32>; 4] = [None, Some(30), Some(90), Some(365)];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Tab {
+    Players,
+    Games,
+}
+
+struct App<'a> {
+    /// All games, in chronological order.
+    all_games: Vec<&'a (GameId, GameStats)>,
+    maps: Vec<String>,
+    /// The selected map, as an index into `maps`.
+    map: Option<usize>,
+    /// The selected date range, as an index into `PERIODS`.
+    period: usize,
+
+    /// The games that match the map and date range.
+    games: Vec<&'a (GameId, GameStats)>,
+    stats: NS2Stats,
+    hive_skills: HashMap<String, HiveSkillHistory>,
+    /// The player names that contain the filter text, in the order of the table.
+    players: Vec<String>,
+    /// Filters the player names, ignoring case.
+    player_filter: String,
+    editing_filter: bool,
+    /// The profile of the selected player, computed when the selection or the games change rather than on every draw.
+    profile: Option<PlayerProfile>,
+    /// The sort column, as an index into `PLAYER_COLUMNS`.
+    sort: usize,
+    ascending: bool,
+
+    tab: Tab,
+    player_state: TableState,
+    game_state: TableState,
+    /// The game that is shown in detail.
+    game: Option<GameSummary>,
+}
+
+impl<'a> App<'a> {
+    fn new(all_games: Vec<&'a (GameId, GameStats)>) -> Self {
+        let maps = all_games.iter().map(|(_, game)| &game.round_info.map_name).collect::<BTreeSet<_>>();
+        let mut app = Self {
+            maps: maps.into_iter().cloned().collect(),
+            all_games,
+            map: None,
+            period: 0,
+            games: Vec::new(),
+            stats: NS2Stats::compute(std::iter::empty()),
+            hive_skills: HashMap::new(),
+            players: Vec::new(),
+            player_filter: String::new(),
+            editing_filter: false,
+            profile: None,
+            sort: PLAYER_COLUMNS.iter().position(|&metric| metric == Metric::AvgScore).unwrap(),
+            ascending: false,
+            tab: Tab::Players,
+            player_state: TableState::default(),
+            game_state: TableState::default(),
+            game: None,
+        };
+        app.refresh();
+        app
+    }
+
+    /// Applies the filters and recomputes the stats.
+    fn refresh(&mut self) {
+        let latest = self.all_games.last().map_or(0, |(_, game)| game.round_info.round_date);
+        let from = PERIODS[self.period].map_or(0, |days| latest.saturating_sub(days * 24 * 60 * 60));
+        let map = self.map.map(|map| &self.maps[map]);
+        self.games = self
+            .all_games
+            .iter()
+            .copied()
+            .filter(|(_, game)| game.round_info.round_date >= from && map.is_none_or(|map| &game.round_info.map_name == map))
+            .collect();
+        self.stats = NS2Stats::compute(self.games.iter().map(|(_, game)| game));
+        self.hive_skills = HiveSkillHistory::by_name(HiveSkillHistory::compute(self.games.iter().map(|(_, game)| game)));
+        self.profile = None;
+        self.sort_players();
+        self.game_state.select((!self.games.is_empty()).then_some(0));
+        self.game = None;
+    }
+
+    fn sort_players(&mut self) {
+        let selected = self.selected_player().map(|name| name.to_owned());
+        let metric = PLAYER_COLUMNS[self.sort];
+        let filter = self.player_filter.to_lowercase();
+        let mut players = self
+            .stats
+            .users
+            .iter()
+            .filter(|(name, _)| name.to_lowercase().contains(&filter))
+            .map(|(name, user)| {
+                (
+                    name,
+                    metric.value(user, self.hive_skills.get(name).and_then(|history| history.current()), Side::Total),
+                )
+            })
+            .collect::<Vec<_>>();
+        players.sort_by(|(name1, key1), (name2, key2)| stats::compare(key1, key2, self.ascending).then_with(|| name1.cmp(name2)));
+        self.players = players.into_iter().map(|(name, _)| name.clone()).collect();
+        let index = selected.and_then(|selected| self.players.iter().position(|name| *name == selected));
+        self.player_state.select(index.or((!self.players.is_empty()).then_some(0)));
+        self.update_profile();
+    }
+
+    /// Computes the profile of the selected player, unless it is already computed.
+    fn update_profile(&mut self) {
+        let selected = self.selected_player();
+        if self.profile.as_ref().map(|profile| profile.name.as_str()) == selected {
+            return;
+        }
+        self.profile = selected.and_then(|name| PlayerProfile::compute(name, self.games.iter().map(|(id, game)| (id, game))));
+    }
+
+    fn selected_player(&self) -> Option<&str> {
+        self.player_state.selected().and_then(|i| self.players.get(i)).map(|name| name.as_str())
+    }
+
+    /// The games, the latest first.
+    fn game_at(&self, index: usize) -> Option<&'a (GameId, GameStats)> {
+        self.games.len().checked_sub(index + 1).map(|i| self.games[i])
+    }
+
+    fn move_selection(&mut self, offset: isize) {
+        let (state, len) = match self.tab {
+            Tab::Players => (&mut self.player_state, self.players.len()),
+            Tab::Games => (&mut self.game_state, self.games.len()),
+        };
+        if len > 0 {
+            let selected = state.selected().unwrap_or(0) as isize + offset;
+            state.select(Some(selected.clamp(0, len as isize - 1) as usize));
+        }
+        if self.tab == Tab::Players {
+            self.update_profile();
+        }
+    }
+
+    /// Handles a key press. Returns `false` if the app should quit.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return false;
+        }
+        if self.editing_filter {
+            match key.code {
+                KeyCode::Enter => self.editing_filter = false,
+                KeyCode::Esc => {
+                    self.editing_filter = false;
+                    self.player_filter.clear();
+                    self.sort_players();
+                }
+                KeyCode::Backspace => {
+                    self.player_filter.pop();
+                    self.sort_players();
+                }
+                KeyCode::Char(c) => {
+                    self.player_filter.push(c);
+                    self.sort_players();
+                }
+                _ => {}
+            }
+            return true;
+        }
+        match key.code {
+            KeyCode::Char('q') => return false,
+            KeyCode::Esc if self.game.is_some() => self.game = None,
+            KeyCode::Esc => return false,
+            KeyCode::Tab | KeyCode::BackTab => {
+                self.tab = match self.tab {
+                    Tab::Players => Tab::Games,
+                    Tab::Games => Tab::Players,
+                };
+                self.game = None;
+            }
+            KeyCode::Char('1') => self.tab = Tab::Players,
+            KeyCode::Char('2') => self.tab = Tab::Games,
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::PageUp => self.move_selection(-10),
+            KeyCode::PageDown => self.move_selection(10),
+            KeyCode::Home | KeyCode::Char('g') => self.move_selection(isize::MIN / 2),
+            KeyCode::End | KeyCode::Char('G') => self.move_selection(isize::MAX / 2),
+            KeyCode::Enter if self.tab == Tab::Games => {
+                let game = self.game_state.selected().and_then(|i| self.game_at(i));
+                self.game = game.map(|(id, game)| summarize_game(id, game));
+            }
+            KeyCode::Char('/') if self.tab == Tab::Players => self.editing_filter = true,
+            KeyCode::Char('s') => {
+                self.sort = (self.sort + 1) % PLAYER_COLUMNS.len();
+                self.sort_players();
+            }
+            KeyCode::Char('r') => {
+                self.ascending = !self.ascending;
+                self.sort_players();
+            }
+            KeyCode::Char('m') => {
+                self.map = match self.map {
+                    None if !self.maps.is_empty() => Some(0),
+                    Some(map) if map + 1 < self.maps.len() => Some(map + 1),
+                    _ => None,
+                };
+                self.refresh();
+            }
+            KeyCode::Char('d') => {
+                self.period = (self.period + 1) % PERIODS.len();
+                self.refresh();
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header, body, footer] = *Layout::new(Direction::Vertical, [Constraint::Length(3), Constraint::Min(0), Constraint::Length(1)]).split(frame.size())
+        else {
+            unreachable!()
+        };
+
+        let mut filters = format!(
+            " map: {}, period: {}, games: {}",
+            self.map.map_or("all", |map| &self.maps[map]),
+            PERIODS[self.period].map_or_else(|| "all".to_owned(), |days| format!("last {} days", days)),
+            self.games.len()
+        );
+        if self.editing_filter || !self.player_filter.is_empty() {
+            filters += &format!(", player: {}{}", self.player_filter, if self.editing_filter { "_" } else { "" });
+        }
+        filters.push(' ');
+        let tabs = Tabs::new(vec!["Players", "Games"])
+            .select(self.tab as usize)
+            .block(Block::default().borders(Borders::ALL).title("ns2-stat").title(filters))
+            .highlight_style(Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED));
+        frame.render_widget(tabs, header);
+
+        let help = match (self.tab, &self.game) {
+            _ if self.editing_filter => "type to filter players  enter done  esc clear",
+            (_, Some(_)) => "q quit  esc back  tab switch",
+            (Tab::Players, None) => "q quit  tab switch  ↑↓ select  / filter  s sort column  r reverse  m map  d period",
+            (Tab::Games, None) => "q quit  tab switch  ↑↓ select  enter details  m map  d period",
+        };
+        frame.render_widget(Paragraph::new(help).style(Style::default().add_modifier(Modifier::DIM)), footer);
+
+        match (self.tab, &self.game) {
+            (_, Some(game)) => draw_game(frame, body, game),
+            (Tab::Players, None) => self.draw_players(frame, body),
+            (Tab::Games, None) => self.draw_games(frame, body),
+        }
+    }
+
+    fn draw_players(&mut self, frame: &mut Frame, area: Rect) {
+        let [table_area, detail_area] = *Layout::new(Direction::Horizontal, [Constraint::Percentage(60), Constraint::Percentage(40)]).split(area) else {
+            unreachable!()
+        };
+
+        let titles = PLAYER_COLUMNS.iter().enumerate().map(|(i, metric)| {
+            let arrow = match (i == self.sort, self.ascending) {
+                (false, _) => "",
+                (true, true) => " ▲",
+                (true, false) => " ▼",
+            };
+            format!("{}{}", metric.title(), arrow)
+        });
+        let header = Row::new(["NAME".to_owned()].into_iter().chain(titles)).style(Style::default().add_modifier(Modifier::BOLD));
+        let rows = self.players.iter().map(|name| {
+            let user = &self.stats.users[name];
+            let hive_skill = self.hive_skills.get(name).and_then(|history| history.current());
+            let values = PLAYER_COLUMNS
+                .iter()
+                .map(|metric| Cell::from(metric.value(user, hive_skill, Side::Total).to_string()));
+            Row::new([Cell::from(name.as_str())].into_iter().chain(values))
+        });
+        let widths = [Constraint::Min(16)].into_iter().chain(PLAYER_COLUMNS.iter().map(|_| Constraint::Length(12)));
+        let table = Table::new(rows, widths)
+            .header(header)
+            .block(Block::default().borders(Borders::ALL).title("Players"))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, table_area, &mut self.player_state);
+
+        let lines = match &self.profile {
+            Some(profile) => player_lines(&self.stats, profile),
+            None => Vec::new(),
+        };
+        frame.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Player")), detail_area);
+    }
+
+    fn draw_games(&mut self, frame: &mut Frame, area: Rect) {
+        let header = Row::new(["DATE", "MAP", "WINNER", "LENGTH", "PLAYERS"]).style(Style::default().add_modifier(Modifier::BOLD));
+        let rows = (0..self.games.len()).filter_map(|i| self.game_at(i)).map(|(_, game)| {
+            let round_info = &game.round_info;
+            Row::new([
+                helpers::format_date(round_info.round_date),
+                round_info.map_name.clone(),
+                format!("{:?}", round_info.winning_team),
+                helpers::format_duration(round_info.round_length),
+                game.player_stats.len().to_string(),
+            ])
+        });
+        let widths = [
+            Constraint::Length(17),
+            Constraint::Min(16),
+            Constraint::Length(8),
+            Constraint::Length(7),
+            Constraint::Length(7),
+        ];
+        let table = Table::new(rows, widths)
+            .header(header)
+            .block(Block::default().borders(Borders::ALL).title("Games"))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, area, &mut self.game_state);
+    }
+}
+
+fn player_lines<'a>(stats: &NS2Stats, profile: &'a PlayerProfile) -> Vec<Line<'a>> {
+    let user = &stats.users[&profile.name];
+    let bold = |text: &'a str| Line::styled(text, Style::default().add_modifier(Modifier::BOLD));
+    let mut lines = vec![bold(&profile.name), Line::default()];
+    let (kd, win_rate) = (user.kd(), user.win_rate());
+    for (side, games, win_rate, kd, commander, commander_wins) in [
+        (
+            "Total",
+            user.games.total,
+            win_rate.total,
+            kd.total,
+            profile.commander_games.total,
+            profile.commander_wins.total,
+        ),
+        (
+            "Marines",
+            user.games.marines,
+            win_rate.marines,
+            kd.marines,
+            profile.commander_games.marines,
+            profile.commander_wins.marines,
+        ),
+        (
+            "Aliens",
+            user.games.aliens,
+            win_rate.aliens,
+            kd.aliens,
+            profile.commander_games.aliens,
+            profile.commander_wins.aliens,
+        ),
+    ] {
+        lines.push(Line::from(format!(
+            "{:<8} {:>4} games  {:>6.2}% WR  {:>5.2} KD  commander {}/{}",
+            side,
+            games,
+            win_rate * 100.0,
+            kd,
+            commander_wins,
+            commander
+        )));
+    }
+    lines.push(Line::default());
+    if let (Some(best), Some(worst)) = (profile.maps.first(), profile.maps.last()) {
+        lines.push(Line::from(format!("Best map: {} ({}/{} won)", best.map_name, best.wins, best.games)));
+        lines.push(Line::from(format!("Worst map: {} ({}/{} won)", worst.map_name, worst.wins, worst.games)));
+    }
+    let weapons = helpers::format_with(profile.weapons.iter().take(3), ", ", |f, weapon| {
+        write!(f, "{} ({})", weapon.weapon, weapon.kills)
+    });
+    lines.push(Line::from(format!("Weapons: {}", weapons)));
+    let lifeforms = helpers::format_with(profile.lifeforms.iter().take(3), ", ", |f, lifeform| write!(f, "{:?}", lifeform.lifeform));
+    lines.push(Line::from(format!("Lifeforms: {}", lifeforms)));
+    let form = profile.form(10);
+    let kd = form.kd.map_or_else(|| "no deaths".to_owned(), |kd| format!("{:.2} KD", kd));
+    lines.push(Line::from(format!("Last {} games: {}/{} won, {}", form.games, form.wins, form.games, kd)));
+    lines.push(Line::default());
+    lines.push(bold("Recent games"));
+    for game in profile.recent_games(5) {
+        lines.push(Line::from(format!(
+            "{} {:<14} {:<7} {:<4} {}/{}/{}",
+            helpers::format_date(game.round_date),
+            game.map_name,
+            match game.team {
+                Team::Marines => "Marines",
+                Team::Aliens => "Aliens",
+            },
+            if game.won { "won" } else { "lost" },
+            game.stats.kills,
+            game.stats.deaths,
+            game.stats.assists
+        )));
+    }
+    lines
+}
+
+fn team_table<'a>(title: &'a str, team: &'a TeamSummary) -> Table<'a> {
+    let mut players = team.players.iter().collect::<Vec<_>>();
+    players.sort_by_key(|(_, player)| std::cmp::Reverse(player.score));
+    let rows = players.into_iter().map(|(name, player)| {
+        let name = if team.is_commander(name) { format!("[{}]", name) } else { name.clone() };
+        Row::new([
+            name,
+            player.kills.to_string(),
+            player.deaths.to_string(),
+            player.assists.to_string(),
+            player.score.to_string(),
+        ])
+    });
+    let widths = [
+        Constraint::Min(16),
+        Constraint::Length(4),
+        Constraint::Length(4),
+        Constraint::Length(4),
+        Constraint::Length(6),
+    ];
+    Table::new(rows, widths)
+        .header(Row::new(["NAME", "K", "D", "A", "SCORE"]).style(Style::default().add_modifier(Modifier::BOLD)))
+        .block(Block::default().borders(Borders::ALL).title(title))
+}
+
+/// Converts an RT graph to the points of a step function, in minutes.
+fn rt_points(rt_graph: &[(f32, u32)]) -> Vec<(f64, f64)> {
+    let mut points = vec![(0.0, 0.0)];
+    for &(time, rt) in rt_graph {
+        let time = time as f64 / 60.0;
+        let previous = points.last().map_or(0.0, |&(_, rt)| rt);
+        points.push((time, previous));
+        points.push((time, rt as f64));
+    }
+    points
+}
+
+fn draw_game(frame: &mut Frame, area: Rect, game: &GameSummary) {
+    let [info, teams, chart] = *Layout::new(Direction::Vertical, [Constraint::Length(3), Constraint::Min(8), Constraint::Length(14)]).split(area) else {
+        unreachable!()
+    };
+    let text = format!(
+        "{}  {}  length {}  winner {:?}",
+        helpers::format_date(game.round_date),
+        game.map_name,
+        helpers::format_duration(game.round_length),
+        game.winning_team
+    );
+    frame.render_widget(
+        Paragraph::new(text).block(Block::default().borders(Borders::ALL).title(game.id.to_string())),
+        info,
+    );
+
+    let [marines, aliens] = *Layout::new(Direction::Horizontal, [Constraint::Percentage(50), Constraint::Percentage(50)]).split(teams) else {
+        unreachable!()
+    };
+    frame.render_widget(team_table("Marines", &game.marines), marines);
+    frame.render_widget(team_table("Aliens", &game.aliens), aliens);
+
+    let marine_points = rt_points(&game.marines.rt_graph);
+    let alien_points = rt_points(&game.aliens.rt_graph);
+    let max_rt = marine_points.iter().chain(&alien_points).map(|&(_, rt)| rt).fold(1.0, f64::max);
+    let length = (game.round_length as f64 / 60.0).max(1.0);
+    let datasets = vec![
+        Dataset::default()
+            .name("Marines")
+            .marker(symbols::Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Cyan))
+            .data(&marine_points),
+        Dataset::default()
+            .name("Aliens")
+            .marker(symbols::Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Yellow))
+            .data(&alien_points),
+    ];
+    let label = |text: String| Span::from(text);
+    let rt_chart = Chart::new(datasets)
+        .block(Block::default().borders(Borders::ALL).title("RTs"))
+        .x_axis(Axis::default().title("minutes").bounds([0.0, length]).labels(vec![
+            label("0".to_owned()),
+            label(format!("{:.0}", length / 2.0)),
+            label(format!("{:.0}", length)),
+        ]))
+        .y_axis(
+            Axis::default()
+                .bounds([0.0, max_rt + 1.0])
+                .labels(vec![label("0".to_owned()), label(format!("{:.0}", max_rt + 1.0))]),
+        );
+    frame.render_widget(rt_chart, chart);
+}
+
+/// Restores the terminal when dropped, also if drawing panics.
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+        let _ = io::stdout().execute(LeaveAlternateScreen);
+    }
+}
+
+/// Runs the interactive terminal UI. The games have to be sorted chronologically.
+pub fn run(games: Vec<&(GameId, GameStats)>) -> io::Result<()> {
+    let mut app = App::new(games);
+
+    terminal::enable_raw_mode()?;
+    let _guard = TerminalGuard;
+    io::stdout().execute(EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+        if !event::poll(Duration::from_millis(250))? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && !app.handle_key(key) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::backend::TestBackend;
+
+    use super::*;
+
+    #[test]
+    fn draw_all_views() {
+        let games = crate::load_data("../test_data", &Default::default()).unwrap();
+        let mut app = App::new(games.iter().collect());
+        let mut terminal = Terminal::new(TestBackend::new(160, 50)).unwrap();
+        let keys = [
+            KeyCode::Char('s'),
+            KeyCode::Down,
+            KeyCode::Tab,
+            KeyCode::Down,
+            KeyCode::Enter,
+            KeyCode::Esc,
+            KeyCode::Char('m'),
+            KeyCode::Char('d'),
+        ];
+        for key in keys {
+            terminal.draw(|frame| app.draw(frame)).unwrap();
+            assert!(app.handle_key(KeyEvent::from(key)));
+        }
+        assert_eq!(app.map, Some(0));
+        assert!(app.games.iter().all(|(_, game)| game.round_info.map_name == app.maps[0]));
+        assert_eq!(app.profile.as_ref().map(|profile| profile.name.as_str()), app.selected_player());
+
+        let mut app = App::new(games.iter().collect());
+        let name = app.players.last().unwrap().clone();
+        app.handle_key(KeyEvent::from(KeyCode::Char('/')));
+        for c in name.to_uppercase().chars() {
+            app.handle_key(KeyEvent::from(KeyCode::Char(c)));
+        }
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        assert!(app.players.contains(&name) && app.players.iter().all(|player| player.to_lowercase().contains(&name.to_lowercase())));
+        assert_eq!(app.profile.as_ref().map(|profile| profile.name.as_str()), app.selected_player());
+        assert!(app.handle_key(KeyEvent::from(KeyCode::Esc)));
+        assert!(app.player_filter.is_empty() && !app.editing_filter);
+    }
+}
@@ -0,0 +1,61 @@
+use ns2_stat::load::{self, GameFile, LoadError};
+use ns2_stat::GameId;
+
+use crate::row;
+use crate::table::{Alignment, Report};
+
+/// Describes why a game file was rejected, without its path.
+pub fn problem(err: LoadError) -> String {
+    match err {
+        LoadError::Parse { source, .. } => format!("failed to parse: {}", source),
+        LoadError::Invalid { reason, .. } => format!("invalid: {}", reason),
+        err => err.to_string(),
+    }
+}
+
+/// Checks every game file individually and reports all problems, instead of stopping at the first one.
+/// Also returns the number of files that could not be parsed or are invalid.
+pub fn validate(files: Vec<GameFile>) -> (Report, usize) {
+    let file_count = files.len();
+    let mut games = Vec::new();
+    let mut problems = Vec::new();
+    for file in files {
+        match load::parse(&file) {
+            Ok(game) => games.push((GameId::new(&game, &file.path), game)),
+            Err(err) => problems.push(row![file.path.display().to_string(), "error", problem(err)]),
+        }
+    }
+    let errors = problems.len();
+    games.sort_by(|(id1, _), (id2, _)| id1.cmp(id2));
+
+    let mut duplicates = 0u32;
+    for pair in games.windows(2) {
+        let [(id1, _), (id2, _)] = pair else { unreachable!() };
+        if id1.same_round(id2) {
+            problems.push(row![id2.to_string(), "warning", format!("same round as `{}`", id1)]);
+            duplicates += 1;
+        }
+    }
+    let not_genuine = games.iter().filter(|(_, game)| !game.is_genuine()).count();
+    let bot_games = games.iter().filter(|(_, game)| game.is_bot_game()).count();
+
+    let mut report = Report::new();
+    report.table(
+        "Problems",
+        ["FILE", "SEVERITY", "PROBLEM"],
+        [Alignment::Left, Alignment::Left, Alignment::Left],
+        problems,
+    );
+    report.fields(
+        "Summary",
+        [
+            ("FILES", file_count.into()),
+            ("VALID GAMES", games.len().into()),
+            ("ERRORS", errors.into()),
+            ("DUPLICATE ROUNDS", duplicates.into()),
+            ("NOT GENUINE", not_genuine.into()),
+            ("BOT GAMES", bot_games.into()),
+        ],
+    );
+    (report, errors)
+}
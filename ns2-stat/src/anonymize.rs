@@ -0,0 +1,127 @@
+//! Pseudonymises games for publishing them.
+//!
+//! Steam IDs, player names and the server are replaced by pseudonyms that are derived from the real values with a
+//! secret salt, so the same player or server gets the same pseudonym in every game, but the real values can't be
+//! recovered without the salt. Everything else is kept, and the result is still a valid [`GameStats`].
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::input_types::{GameStats, SteamId};
+
+/// Pseudonymous Steam IDs stay below 2^52, so they are exact in JSON parsers that use doubles.
+const STEAM_ID_MASK: u64 = (1 << 52) - 1;
+
+pub struct Anonymizer {
+    mac: Hmac<Sha256>,
+}
+
+impl Anonymizer {
+    pub fn new(salt: &[u8]) -> Self {
+        Self {
+            mac: Hmac::new_from_slice(salt).expect("HMAC accepts keys of any length"),
+        }
+    }
+
+    /// A keyed hash of `value`. The `domain` keeps the hashes of different kinds of values apart.
+    fn hash(&self, domain: &str, value: &[u8]) -> [u8; 32] {
+        let mut mac = self.mac.clone();
+        mac.update(domain.as_bytes());
+        mac.update(&[0]);
+        mac.update(value);
+        mac.finalize().into_bytes().into()
+    }
+
+    /// The pseudonym of a Steam ID. Bots have the Steam ID 0, which is kept.
+    pub fn steam_id(&self, steam_id: SteamId) -> SteamId {
+        if steam_id == 0 {
+            return 0;
+        }
+        let hash = self.hash("steam id", &steam_id.to_le_bytes());
+        let id = u64::from_le_bytes(hash[..8].try_into().unwrap()) & STEAM_ID_MASK;
+        id.max(1) as SteamId
+    }
+
+    /// The name of a player, derived from the Steam ID, so renamed players keep their pseudonym.
+    pub fn player_name(&self, steam_id: SteamId) -> String {
+        if steam_id == 0 {
+            return "Bot".to_owned();
+        }
+        format!("Player {:013x}", self.steam_id(steam_id))
+    }
+
+    /// The pseudonym of a server, used for both its IP and its name.
+    fn server(&self, ip: &str, port: u16) -> String {
+        let hash = self.hash("server", format!("{}:{}", ip, port).as_bytes());
+        hash[..4].iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Replaces the Steam IDs, player names and the server IP and name of the game by pseudonyms. The server port is
+    /// removed.
+    pub fn game(&self, game: &GameStats) -> GameStats {
+        let mut game = game.clone();
+        game.player_stats = game
+            .player_stats
+            .into_iter()
+            .map(|(steam_id, mut player)| {
+                player.player_name = self.player_name(steam_id);
+                (self.steam_id(steam_id), player)
+            })
+            .collect();
+        for kill in &mut game.kill_feed {
+            kill.killer_steam_id = kill.killer_steam_id.map(|steam_id| self.steam_id(steam_id));
+            kill.victim_steam_id = self.steam_id(kill.victim_steam_id);
+        }
+        // the marine commander stats are keyed by the Steam ID as a string
+        game.marine_comm_stats = game
+            .marine_comm_stats
+            .into_iter()
+            .map(|(steam_id, stats)| {
+                let steam_id = match steam_id.parse() {
+                    Ok(steam_id) => self.steam_id(steam_id).to_string(),
+                    Err(_) => format!("{:x}", u64::from_le_bytes(self.hash("steam id", steam_id.as_bytes())[..8].try_into().unwrap())),
+                };
+                (steam_id, stats)
+            })
+            .collect();
+
+        let server = self.server(&game.server_info.ip, game.server_info.port);
+        game.server_info.name = format!("Server {}", server);
+        game.server_info.ip = server;
+        game.server_info.port = 0;
+        game
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load;
+
+    #[test]
+    fn pseudonyms_are_consistent() {
+        let games = load::load("../test_data").unwrap().games;
+        let anonymizer = Anonymizer::new(b"secret");
+        let (real_id, player) = games.values().flat_map(|game| &game.player_stats).find(|(&steam_id, _)| steam_id != 0).unwrap();
+
+        for game in games.values().take(20) {
+            let anonymized = anonymizer.game(game);
+            let json = serde_json::to_string(&anonymized).unwrap();
+            assert!(!json.contains(&game.server_info.ip));
+            for (steam_id, player) in &game.player_stats {
+                if *steam_id != 0 {
+                    assert!(!json.contains(&format!("\"{}\"", player.player_name)));
+                    assert!(!anonymized.player_stats.contains_key(steam_id));
+                }
+            }
+            // the result is still a valid game
+            let parsed = serde_json::from_str::<GameStats>(&json).unwrap();
+            assert_eq!(parsed.player_stats.len(), game.player_stats.len());
+            if let Some(anonymized_player) = parsed.player_stats.get(&anonymizer.steam_id(*real_id)) {
+                assert_eq!(anonymized_player.player_name, anonymizer.player_name(*real_id));
+                assert_ne!(anonymized_player.player_name, player.player_name);
+            }
+        }
+        assert_ne!(anonymizer.steam_id(*real_id), Anonymizer::new(b"other").steam_id(*real_id));
+    }
+}
@@ -0,0 +1,173 @@
+//! Configuration of which players and games the stats include, shared by all tools.
+//!
+//! The configuration is a TOML file:
+//!
+//! ```toml
+//! # a player with several accounts or names, shown as `name`
+//! [[players]]
+//! name = "Alice"
+//! steam_ids = [123456, 234567]
+//! # names that belong to this player regardless of the Steam ID
+//! aliases = ["alice2"]
+//!
+//! [exclude]
+//! # bots and test accounts
+//! steam_ids = [345678]
+//! names = ["TestBot"]
+//! # games by their ID, e.g. aborted scrims
+//! games = ["1629228969-77.179.12.31-27015-1629228969.json"]
+//! ```
+//!
+//! A player entry with a single Steam ID overrides the name of that account. The configuration is applied to the games
+//! right after loading them, so everything computed from them, like [`NS2Stats`](crate::NS2Stats) and
+//! [`summarize_game`](crate::summarize_game), sees the same players.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::input_types::{GameStats, SteamId};
+use crate::load::LoadError;
+use crate::GameId;
+
+/// The name of the configuration file in the data directory, which is used if no other file is given.
+pub const DEFAULT_FILE_NAME: &str = "ns2-stat.toml";
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    players: Vec<Player>,
+    #[serde(default)]
+    exclude: Exclude,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Player {
+    name: String,
+    #[serde(default)]
+    steam_ids: Vec<SteamId>,
+    #[serde(default)]
+    aliases: Vec<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Exclude {
+    #[serde(default)]
+    steam_ids: HashSet<SteamId>,
+    #[serde(default)]
+    names: HashSet<String>,
+    #[serde(default)]
+    games: HashSet<GameId>,
+}
+
+#[derive(Default)]
+pub struct Config {
+    names_by_steam_id: HashMap<SteamId, String>,
+    names_by_alias: HashMap<String, String>,
+    exclude: Exclude,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Self, toml::de::Error> {
+        let ConfigFile { players, exclude } = toml::from_str(text)?;
+        let mut config = Self { exclude, ..Self::default() };
+        for player in players {
+            for steam_id in player.steam_ids {
+                config.names_by_steam_id.insert(steam_id, player.name.clone());
+            }
+            for alias in player.aliases {
+                config.names_by_alias.insert(alias, player.name.clone());
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(LoadError::io(path))?;
+        Self::parse(&text).map_err(|source| LoadError::Config { path: path.to_owned(), source })
+    }
+
+    /// Loads the configuration at `path`, or the default file in the data directory at `data_path` if it exists.
+    /// Without either, nothing is changed.
+    pub fn load_or_default<P: AsRef<Path>, D: AsRef<Path>>(path: Option<P>, data_path: D) -> Result<Self, LoadError> {
+        match path {
+            Some(path) => Self::load(path),
+            None => {
+                let path = data_path.as_ref().join(DEFAULT_FILE_NAME);
+                if path.is_file() {
+                    Self::load(path)
+                } else {
+                    Ok(Self::default())
+                }
+            }
+        }
+    }
+
+    pub fn is_excluded(&self, id: &GameId) -> bool {
+        self.exclude.games.contains(id)
+    }
+
+    /// Removes the excluded players from the game and gives the others their configured name.
+    pub fn apply_to_game(&self, game: &mut GameStats) {
+        game.player_stats
+            .retain(|steam_id, player| !self.exclude.steam_ids.contains(steam_id) && !self.exclude.names.contains(&player.player_name));
+        for (steam_id, player) in &mut game.player_stats {
+            let name = self.names_by_steam_id.get(steam_id).or_else(|| self.names_by_alias.get(&player.player_name));
+            if let Some(name) = name {
+                player.player_name = name.clone();
+            }
+        }
+    }
+
+    /// Removes the excluded games and applies the configuration to the others.
+    pub fn apply(&self, games: &mut BTreeMap<GameId, GameStats>) {
+        games.retain(|id, _| !self.is_excluded(id));
+        for game in games.values_mut() {
+            self.apply_to_game(game);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load;
+    use crate::NS2Stats;
+
+    #[test]
+    fn merge_and_exclude() {
+        let mut games = load::load("../test_data").unwrap().games;
+        let (excluded_id, excluded_game) = games.iter().next().map(|(id, game)| (id.clone(), game.clone())).unwrap();
+        let mut players = excluded_game.player_stats.iter().filter(|(&steam_id, _)| steam_id != 0).collect::<Vec<_>>();
+        players.sort_by_key(|(&steam_id, _)| steam_id);
+        let [(&steam_id1, player1), (&steam_id2, player2), (_, player3), ..] = players[..] else {
+            panic!("not enough players");
+        };
+        let config = Config::parse(&format!(
+            "[[players]]\nname = \"Merged\"\nsteam_ids = [{}, {}]\n\n[exclude]\nnames = [\"{}\"]\ngames = [\"{}\"]\n",
+            steam_id1, steam_id2, player3.player_name, excluded_id
+        ))
+        .unwrap();
+        config.apply(&mut games);
+        let stats = NS2Stats::compute(games.values());
+
+        assert!(!games.contains_key(&excluded_id));
+        assert!(!stats.users.contains_key(&player1.player_name) && !stats.users.contains_key(&player2.player_name));
+        assert!(!stats.users.contains_key(&player3.player_name));
+        let accounts = games
+            .values()
+            .flat_map(|game| game.player_stats.keys())
+            .filter(|&&steam_id| steam_id == steam_id1 || steam_id == steam_id2)
+            .count();
+        assert_eq!(stats.users["Merged"].games.total as usize, accounts);
+
+        assert!(Config::parse("[exclude]\ngames = [\"not an id\"]").is_err());
+        assert!(Config::parse("[[player]]\nname = \"typo\"").is_err());
+    }
+}
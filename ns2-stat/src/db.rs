@@ -0,0 +1,867 @@
+//! A SQLite store for games, enabled by the `sqlite` feature.
+//!
+//! Each game is normalised into tables, so the archive can be queried with SQL and the stats can be computed without
+//! keeping all games in memory:
+//!
+//! - `rounds`: one row per game with the round and server info, `id` is referenced by all other tables
+//! - `locations` and `mods`: the location names and server mods of a round
+//! - `players`: one row per player per round, and `player_teams` with the stats of a player on each team
+//! - `player_classes` and `weapons`: the time per class and the stats per weapon of a player
+//! - `kills`, `buildings` and `research`: the events of a round
+//! - `marine_comm_stats`: the medpacks, ammopacks and catpacks dropped by each marine commander
+//!
+//! The games can be read back exactly as they were stored, except that SQLite doesn't keep the sign of zero.
+//!
+//! The CLI's `store` command writes the database. The CLI and the API can both serve the games from it instead of a
+//! data path, which is meant for archives that are too large to keep in memory.
+
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::path::Path;
+
+use rusqlite::types::{FromSql, ToSql};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row, Transaction};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::filter::GameFilter;
+use crate::input_types::{
+    Ammopack, Building, Catpack, GameStats, KillFeed, MarineCommStat, Medpack, MinimapExtents, Mod, PlayerStat, PlayerTeamStats, Position, Research, RoundInfo,
+    ServerInfo, StartingLocations, Status, Team, Weapon, WinningTeam,
+};
+use crate::{GameId, NS2Stats};
+
+/// Incremented whenever the schema changes. Stored as the `user_version` of the database.
+const SCHEMA_VERSION: u32 = 1;
+
+const SCHEMA: &str = "
+CREATE TABLE rounds (
+    id INTEGER PRIMARY KEY,
+    game_id TEXT NOT NULL UNIQUE,
+    round_date INTEGER NOT NULL,
+    file_name TEXT NOT NULL,
+    map_name TEXT NOT NULL,
+    round_length REAL NOT NULL,
+    winning_team INTEGER NOT NULL,
+    tournament_mode INTEGER NOT NULL,
+    max_players_marines INTEGER NOT NULL,
+    max_players_aliens INTEGER NOT NULL,
+    minimap_origin TEXT NOT NULL,
+    minimap_scale TEXT NOT NULL,
+    marine_start_location INTEGER NOT NULL,
+    alien_start_location INTEGER NOT NULL,
+    server_ip TEXT NOT NULL,
+    server_port INTEGER NOT NULL,
+    server_name TEXT NOT NULL,
+    slots INTEGER NOT NULL,
+    rookie_only INTEGER NOT NULL,
+    build_number INTEGER NOT NULL
+);
+CREATE INDEX rounds_round_date ON rounds (round_date);
+CREATE INDEX rounds_map_name ON rounds (map_name);
+
+CREATE TABLE locations (
+    round_id INTEGER NOT NULL REFERENCES rounds (id),
+    location INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    PRIMARY KEY (round_id, location)
+);
+
+CREATE TABLE mods (
+    round_id INTEGER NOT NULL REFERENCES rounds (id),
+    position INTEGER NOT NULL,
+    mod_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    PRIMARY KEY (round_id, position)
+);
+
+CREATE TABLE players (
+    round_id INTEGER NOT NULL REFERENCES rounds (id),
+    steam_id INTEGER NOT NULL,
+    player_name TEXT NOT NULL,
+    is_rookie INTEGER NOT NULL,
+    last_team INTEGER NOT NULL,
+    hive_skill INTEGER NOT NULL,
+    commander_skill INTEGER,
+    commander_skill_offset INTEGER,
+    player_skill_offset INTEGER,
+    PRIMARY KEY (round_id, steam_id)
+);
+CREATE INDEX players_player_name ON players (player_name);
+
+CREATE TABLE player_teams (
+    round_id INTEGER NOT NULL REFERENCES rounds (id),
+    steam_id INTEGER NOT NULL,
+    team INTEGER NOT NULL,
+    kills INTEGER NOT NULL,
+    deaths INTEGER NOT NULL,
+    assists INTEGER NOT NULL,
+    score INTEGER NOT NULL,
+    time_building REAL NOT NULL,
+    hits INTEGER NOT NULL,
+    onos_hits INTEGER NOT NULL,
+    misses INTEGER NOT NULL,
+    killstreak INTEGER NOT NULL,
+    time_played REAL NOT NULL,
+    commander_time REAL NOT NULL,
+    player_damage REAL NOT NULL,
+    structure_damage REAL NOT NULL,
+    PRIMARY KEY (round_id, steam_id, team)
+);
+
+CREATE TABLE player_classes (
+    round_id INTEGER NOT NULL REFERENCES rounds (id),
+    steam_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    class TEXT NOT NULL,
+    class_time REAL NOT NULL,
+    PRIMARY KEY (round_id, steam_id, position)
+);
+
+CREATE TABLE weapons (
+    round_id INTEGER NOT NULL REFERENCES rounds (id),
+    steam_id INTEGER NOT NULL,
+    weapon TEXT NOT NULL,
+    team INTEGER NOT NULL,
+    kills INTEGER NOT NULL,
+    onos_hits INTEGER NOT NULL,
+    player_damage REAL NOT NULL,
+    hits INTEGER NOT NULL,
+    structure_damage REAL NOT NULL,
+    misses INTEGER NOT NULL,
+    PRIMARY KEY (round_id, steam_id, weapon)
+);
+
+CREATE TABLE kills (
+    round_id INTEGER NOT NULL REFERENCES rounds (id),
+    position INTEGER NOT NULL,
+    game_time REAL NOT NULL,
+    killer_weapon TEXT NOT NULL,
+    killer_steam_id INTEGER,
+    killer_team INTEGER NOT NULL,
+    killer_class TEXT,
+    killer_location INTEGER,
+    killer_x REAL,
+    killer_y REAL,
+    killer_z REAL,
+    doer_location INTEGER,
+    doer_x REAL,
+    doer_y REAL,
+    doer_z REAL,
+    victim_steam_id INTEGER NOT NULL,
+    victim_class TEXT NOT NULL,
+    victim_location INTEGER,
+    victim_x REAL NOT NULL,
+    victim_y REAL NOT NULL,
+    victim_z REAL NOT NULL,
+    PRIMARY KEY (round_id, position)
+);
+
+CREATE TABLE buildings (
+    round_id INTEGER NOT NULL REFERENCES rounds (id),
+    position INTEGER NOT NULL,
+    game_time REAL NOT NULL,
+    team INTEGER NOT NULL,
+    tech_id TEXT NOT NULL,
+    event TEXT,
+    built INTEGER NOT NULL,
+    recycled INTEGER NOT NULL,
+    destroyed INTEGER NOT NULL,
+    biomass INTEGER,
+    entity_id INTEGER,
+    x REAL NOT NULL,
+    y REAL NOT NULL,
+    z REAL NOT NULL,
+    PRIMARY KEY (round_id, position)
+);
+
+CREATE TABLE research (
+    round_id INTEGER NOT NULL REFERENCES rounds (id),
+    position INTEGER NOT NULL,
+    game_time REAL NOT NULL,
+    team INTEGER NOT NULL,
+    research_id TEXT NOT NULL,
+    PRIMARY KEY (round_id, position)
+);
+
+CREATE TABLE marine_comm_stats (
+    round_id INTEGER NOT NULL REFERENCES rounds (id),
+    steam_id TEXT NOT NULL,
+    medpack_picks INTEGER NOT NULL,
+    medpack_misses INTEGER NOT NULL,
+    medpack_refilled REAL NOT NULL,
+    medpack_hits_acc INTEGER NOT NULL,
+    ammopack_picks INTEGER NOT NULL,
+    ammopack_misses INTEGER NOT NULL,
+    ammopack_refilled REAL NOT NULL,
+    catpack_picks INTEGER NOT NULL,
+    catpack_misses INTEGER NOT NULL,
+    PRIMARY KEY (round_id, steam_id)
+);
+";
+
+#[derive(Debug)]
+pub enum Error {
+    Sqlite(rusqlite::Error),
+    /// The database was created by another version, with the given schema version.
+    Schema(u32),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Sqlite(_) => f.write_str("database error"),
+            Error::Schema(version) => write!(f, "unsupported database schema version {}, expected {}", version, SCHEMA_VERSION),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Sqlite(err) => Some(err),
+            Error::Schema(_) => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(err: rusqlite::Error) -> Self {
+        Error::Sqlite(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+pub struct Database {
+    connection: Connection,
+}
+
+impl Database {
+    /// Opens the database at `path`, and creates it if it doesn't exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(connection: Connection) -> Result<Self> {
+        connection.pragma_update(None, "foreign_keys", true)?;
+        match connection.pragma_query_value(None, "user_version", |row| row.get(0))? {
+            0 => {
+                connection.execute_batch(SCHEMA)?;
+                connection.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+            }
+            SCHEMA_VERSION => {}
+            version => return Err(Error::Schema(version)),
+        }
+        Ok(Self { connection })
+    }
+
+    /// Stores the games that are not stored yet, in a single transaction. Returns the number of added games.
+    pub fn insert_games<'a, I: IntoIterator<Item = (&'a GameId, &'a GameStats)>>(&mut self, games: I) -> Result<usize> {
+        let transaction = self.connection.transaction()?;
+        let mut added = 0;
+        for (id, game) in games {
+            if insert_game(&transaction, id, game)? {
+                added += 1;
+            }
+        }
+        transaction.commit()?;
+        Ok(added)
+    }
+
+    /// The stored game with this ID.
+    pub fn game(&self, id: &GameId) -> Result<Option<GameStats>> {
+        let round = self
+            .connection
+            .query_row("SELECT id FROM rounds WHERE game_id = ?", [id.to_string()], |row| row.get::<_, i64>(0))
+            .optional()?;
+        round.map(|round| read_game(&self.connection, round)).transpose()
+    }
+
+    /// The IDs of all stored games, sorted.
+    pub fn ids(&self) -> Result<Vec<GameId>> {
+        let mut statement = self.connection.prepare("SELECT game_id FROM rounds")?;
+        let mut ids = statement
+            .query_map([], |row| row.get::<_, String>(0))?
+            .map(|id| parse_id(&id?))
+            .collect::<Result<Vec<_>>>()?;
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// Calls `f` with each game that matches the filter, in the order of their IDs, without reading all games at once.
+    pub fn for_each_game(&self, filter: &GameFilter, mut f: impl FnMut(GameId, GameStats)) -> Result<()> {
+        // the cheap criteria are checked in SQL, the rest once the game is read
+        let mut conditions = vec!["1".to_owned()];
+        let mut params = Vec::<Box<dyn ToSql>>::new();
+        if let Some(from) = filter.from {
+            conditions.push("round_date >= ?".to_owned());
+            params.push(Box::new(from));
+        }
+        if let Some(to) = filter.to {
+            conditions.push("round_date <= ?".to_owned());
+            params.push(Box::new(to));
+        }
+        if let Some(map) = &filter.map {
+            conditions.push("map_name = ?".to_owned());
+            params.push(Box::new(map.clone()));
+        }
+        if let Some(min_length) = filter.min_length {
+            conditions.push("round_length >= ?".to_owned());
+            params.push(Box::new(min_length));
+        }
+        if let Some(winner) = filter.winner {
+            conditions.push("winning_team = ?".to_owned());
+            params.push(Box::new(WinningTeam::from(winner) as u8));
+        }
+        if let Some(player) = &filter.player {
+            conditions.push("EXISTS (SELECT 1 FROM players WHERE round_id = rounds.id AND player_name = ?)".to_owned());
+            params.push(Box::new(player.clone()));
+        }
+
+        let mut statement = self
+            .connection
+            .prepare(&format!("SELECT id, game_id FROM rounds WHERE {}", conditions.join(" AND ")))?;
+        let mut rounds = statement
+            .query_map(params_from_iter(params), |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+            .map(|round| {
+                let (round, id) = round?;
+                Ok((parse_id(&id)?, round))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        rounds.sort();
+        for (id, round) in rounds {
+            let game = read_game(&self.connection, round)?;
+            if filter.matches(&game) {
+                f(id, game);
+            }
+        }
+        Ok(())
+    }
+
+    /// All games that match the filter, sorted by their ID.
+    pub fn games(&self, filter: &GameFilter) -> Result<Vec<(GameId, GameStats)>> {
+        let mut games = Vec::new();
+        self.for_each_game(filter, |id, game| games.push((id, game)))?;
+        Ok(games)
+    }
+
+    /// Computes the stats of the games that match the filter, reading one game at a time.
+    pub fn stats(&self, filter: &GameFilter) -> Result<NS2Stats> {
+        let mut stats = NS2Stats::default();
+        self.for_each_game(filter, |_, game| stats.add_game(&game))?;
+        Ok(stats)
+    }
+}
+
+fn parse_id(id: &str) -> Result<GameId> {
+    Ok(id
+        .parse::<GameId>()
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))?)
+}
+
+fn team_to_sql(team: Team) -> u8 {
+    team as u8
+}
+
+fn team_from_sql(row: &Row, column: &str) -> rusqlite::Result<Team> {
+    match row.get::<_, u8>(column)? {
+        1 => Ok(Team::Marines),
+        2 => Ok(Team::Aliens),
+        team => Err(rusqlite::Error::IntegralValueOutOfRange(row.as_ref().column_index(column)?, team.into())),
+    }
+}
+
+/// Stores enums like [`PlayerClass`](crate::input_types::PlayerClass) by their name, like in the game files.
+fn name_to_sql<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|value| value.as_str().map(|name| name.to_owned()))
+        .unwrap_or_default()
+}
+
+fn name_from_sql<T: DeserializeOwned>(row: &Row, column: &str) -> rusqlite::Result<T> {
+    let name = row.get::<_, String>(column)?;
+    serde_json::from_value(serde_json::Value::String(name))
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(row.as_ref().column_index(column).unwrap_or_default(), rusqlite::types::Type::Text, Box::new(e)))
+}
+
+fn optional_name_from_sql<T: DeserializeOwned>(row: &Row, column: &str) -> rusqlite::Result<Option<T>> {
+    match row.get_ref(column)? {
+        rusqlite::types::ValueRef::Null => Ok(None),
+        _ => name_from_sql(row, column).map(Some),
+    }
+}
+
+/// Reads the position from the columns `<prefix>x`, `<prefix>y` and `<prefix>z`.
+fn position_from_sql(row: &Row, prefix: &str) -> rusqlite::Result<Option<Position>> {
+    let [x, y, z] = ["x", "y", "z"].map(|axis| row.get::<_, Option<f32>>(&*format!("{}{}", prefix, axis)));
+    Ok(match (x?, y?, z?) {
+        (Some(x), Some(y), Some(z)) => Some(Position { x, y, z }),
+        _ => None,
+    })
+}
+
+fn position_to_sql(position: Option<&Position>) -> [Option<f32>; 3] {
+    [position.map(|p| p.x), position.map(|p| p.y), position.map(|p| p.z)]
+}
+
+fn get<T: FromSql>(row: &Row, column: &str) -> rusqlite::Result<T> {
+    row.get(column)
+}
+
+/// Stores a game, unless a game with the same ID is already stored. Returns `true` if it was stored.
+fn insert_game(transaction: &Transaction, id: &GameId, game: &GameStats) -> Result<bool> {
+    let GameStats {
+        kill_feed,
+        locations,
+        research,
+        buildings,
+        player_stats,
+        round_info,
+        server_info,
+        marine_comm_stats,
+    } = game;
+    let inserted = transaction
+        .prepare_cached(
+            "INSERT OR IGNORE INTO rounds (game_id, round_date, file_name, map_name, round_length, winning_team, tournament_mode, max_players_marines,
+            max_players_aliens, minimap_origin, minimap_scale, marine_start_location, alien_start_location, server_ip, server_port, server_name, slots,
+            rookie_only, build_number)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )?
+        .execute(params![
+            id.to_string(),
+            round_info.round_date,
+            id.file_name,
+            round_info.map_name,
+            round_info.round_length,
+            round_info.winning_team as u8,
+            round_info.tournament_mode,
+            round_info.max_players_marines,
+            round_info.max_players_aliens,
+            round_info.minimap_extents.origin,
+            round_info.minimap_extents.scale,
+            round_info.starting_locations.marines,
+            round_info.starting_locations.aliens,
+            server_info.ip,
+            server_info.port,
+            server_info.name,
+            server_info.slots,
+            server_info.rookie_only,
+            server_info.build_number,
+        ])?;
+    if inserted == 0 {
+        return Ok(false);
+    }
+    let round = transaction.last_insert_rowid();
+
+    let mut statement = transaction.prepare_cached("INSERT INTO locations (round_id, location, name) VALUES (?, ?, ?)")?;
+    for (i, name) in locations.iter().enumerate() {
+        statement.execute(params![round, i + 1, name])?;
+    }
+    let mut statement = transaction.prepare_cached("INSERT INTO mods (round_id, position, mod_id, name) VALUES (?, ?, ?, ?)")?;
+    for (i, Mod { mod_id, name }) in server_info.mods.iter().enumerate() {
+        statement.execute(params![round, i, mod_id, name])?;
+    }
+
+    for (steam_id, player) in player_stats {
+        transaction
+            .prepare_cached(
+                "INSERT INTO players (round_id, steam_id, player_name, is_rookie, last_team, hive_skill, commander_skill, commander_skill_offset,
+                    player_skill_offset)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )?
+            .execute(params![
+                round,
+                steam_id,
+                player.player_name,
+                player.is_rookie,
+                team_to_sql(player.last_team),
+                player.hive_skill,
+                player.commander_skill,
+                player.commander_skill_offset,
+                player.player_skill_offset,
+            ])?;
+        let mut statement = transaction.prepare_cached(
+            "INSERT INTO player_teams (round_id, steam_id, team, kills, deaths, assists, score, time_building, hits, onos_hits, misses, killstreak,
+                time_played, commander_time, player_damage, structure_damage)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )?;
+        for (team, stats) in [(Team::Marines, &player.marines), (Team::Aliens, &player.aliens)] {
+            statement.execute(params![
+                round,
+                steam_id,
+                team_to_sql(team),
+                stats.kills,
+                stats.deaths,
+                stats.assists,
+                stats.score,
+                stats.time_building,
+                stats.hits,
+                stats.onos_hits,
+                stats.misses,
+                stats.killstreak,
+                stats.time_played,
+                stats.commander_time,
+                stats.player_damage,
+                stats.structure_damage,
+            ])?;
+        }
+        let mut statement =
+            transaction.prepare_cached("INSERT INTO player_classes (round_id, steam_id, position, class, class_time) VALUES (?, ?, ?, ?, ?)")?;
+        for (i, status) in player.status.iter().enumerate() {
+            statement.execute(params![round, steam_id, i, name_to_sql(&status.status_id), status.class_time])?;
+        }
+        let mut statement = transaction.prepare_cached(
+            "INSERT INTO weapons (round_id, steam_id, weapon, team, kills, onos_hits, player_damage, hits, structure_damage, misses)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )?;
+        for (name, weapon) in &player.weapons {
+            statement.execute(params![
+                round,
+                steam_id,
+                name,
+                team_to_sql(weapon.team),
+                weapon.kills,
+                weapon.onos_hits,
+                weapon.player_damage,
+                weapon.hits,
+                weapon.structure_damage,
+                weapon.misses,
+            ])?;
+        }
+    }
+
+    let mut statement = transaction.prepare_cached(
+        "INSERT INTO kills (round_id, position, game_time, killer_weapon, killer_steam_id, killer_team, killer_class, killer_location, killer_x, killer_y,
+            killer_z, doer_location, doer_x, doer_y, doer_z, victim_steam_id, victim_class, victim_location, victim_x, victim_y, victim_z)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )?;
+    for (i, kill) in kill_feed.iter().enumerate() {
+        let [killer_x, killer_y, killer_z] = position_to_sql(kill.killer_position.as_ref());
+        let [doer_x, doer_y, doer_z] = position_to_sql(kill.doer_position.as_ref());
+        statement.execute(params![
+            round,
+            i,
+            kill.game_time,
+            kill.killer_weapon,
+            kill.killer_steam_id,
+            team_to_sql(kill.killer_team),
+            kill.killer_class.as_ref().map(name_to_sql),
+            kill.killer_location,
+            killer_x,
+            killer_y,
+            killer_z,
+            kill.doer_location,
+            doer_x,
+            doer_y,
+            doer_z,
+            kill.victim_steam_id,
+            name_to_sql(&kill.victim_class),
+            kill.victim_location,
+            kill.victim_position.x,
+            kill.victim_position.y,
+            kill.victim_position.z,
+        ])?;
+    }
+
+    let mut statement = transaction.prepare_cached(
+        "INSERT INTO buildings (round_id, position, game_time, team, tech_id, event, built, recycled, destroyed, biomass, entity_id, x, y, z)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )?;
+    for (i, building) in buildings.iter().enumerate() {
+        statement.execute(params![
+            round,
+            i,
+            building.game_time,
+            team_to_sql(building.team),
+            building.tech_id,
+            building.event.as_ref().map(name_to_sql),
+            building.built,
+            building.recycled,
+            building.destroyed,
+            building.biomass,
+            building.entity_id,
+            building.location.x,
+            building.location.y,
+            building.location.z,
+        ])?;
+    }
+
+    let mut statement = transaction.prepare_cached("INSERT INTO research (round_id, position, game_time, team, research_id) VALUES (?, ?, ?, ?, ?)")?;
+    for (i, research) in research.iter().enumerate() {
+        statement.execute(params![round, i, research.game_time, team_to_sql(research.team), research.research_id])?;
+    }
+
+    let mut statement = transaction.prepare_cached(
+        "INSERT INTO marine_comm_stats (round_id, steam_id, medpack_picks, medpack_misses, medpack_refilled, medpack_hits_acc, ammopack_picks,
+            ammopack_misses, ammopack_refilled, catpack_picks, catpack_misses)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )?;
+    for (steam_id, MarineCommStat { medpack, ammopack, catpack }) in marine_comm_stats {
+        statement.execute(params![
+            round,
+            steam_id,
+            medpack.picks,
+            medpack.misses,
+            medpack.refilled,
+            medpack.hits_acc,
+            ammopack.picks,
+            ammopack.misses,
+            ammopack.refilled,
+            catpack.picks,
+            catpack.misses,
+        ])?;
+    }
+    Ok(true)
+}
+
+/// Reads the rows of a table that belong to a round, in the order of `order_by`.
+fn read_rows<T>(connection: &Connection, table: &str, round: i64, order_by: &str, f: impl FnMut(&Row) -> rusqlite::Result<T>) -> Result<Vec<T>> {
+    let mut statement = connection.prepare_cached(&format!("SELECT * FROM {} WHERE round_id = ? ORDER BY {}", table, order_by))?;
+    let rows = statement.query_map([round], f)?.collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(rows)
+}
+
+fn read_game(connection: &Connection, round: i64) -> Result<GameStats> {
+    let (round_info, mut server_info) = connection.prepare_cached("SELECT * FROM rounds WHERE id = ?")?.query_row([round], |row| {
+        let winning_team = match get::<u8>(row, "winning_team")? {
+            1 => WinningTeam::Marines,
+            2 => WinningTeam::Aliens,
+            _ => WinningTeam::None,
+        };
+        let round_info = RoundInfo {
+            round_date: get(row, "round_date")?,
+            max_players_marines: get(row, "max_players_marines")?,
+            max_players_aliens: get(row, "max_players_aliens")?,
+            minimap_extents: MinimapExtents {
+                origin: get(row, "minimap_origin")?,
+                scale: get(row, "minimap_scale")?,
+            },
+            starting_locations: StartingLocations {
+                marines: get(row, "marine_start_location")?,
+                aliens: get(row, "alien_start_location")?,
+            },
+            winning_team,
+            tournament_mode: get(row, "tournament_mode")?,
+            round_length: get(row, "round_length")?,
+            map_name: get(row, "map_name")?,
+        };
+        let server_info = ServerInfo {
+            mods: Vec::new(),
+            slots: get(row, "slots")?,
+            rookie_only: get(row, "rookie_only")?,
+            build_number: get(row, "build_number")?,
+            ip: get(row, "server_ip")?,
+            name: get(row, "server_name")?,
+            port: get(row, "server_port")?,
+        };
+        Ok((round_info, server_info))
+    })?;
+    server_info.mods = read_rows(connection, "mods", round, "position", |row| {
+        Ok(Mod {
+            mod_id: get(row, "mod_id")?,
+            name: get(row, "name")?,
+        })
+    })?;
+    let locations = read_rows(connection, "locations", round, "location", |row| get(row, "name"))?;
+
+    let mut team_stats = HashMap::new();
+    for (steam_id, team, stats) in read_rows(connection, "player_teams", round, "steam_id, team", |row| {
+        let stats = PlayerTeamStats {
+            kills: get(row, "kills")?,
+            deaths: get(row, "deaths")?,
+            assists: get(row, "assists")?,
+            score: get(row, "score")?,
+            time_building: get(row, "time_building")?,
+            hits: get(row, "hits")?,
+            onos_hits: get(row, "onos_hits")?,
+            misses: get(row, "misses")?,
+            killstreak: get(row, "killstreak")?,
+            time_played: get(row, "time_played")?,
+            commander_time: get(row, "commander_time")?,
+            player_damage: get(row, "player_damage")?,
+            structure_damage: get(row, "structure_damage")?,
+        };
+        Ok((get::<i64>(row, "steam_id")?, get::<u8>(row, "team")?, stats))
+    })? {
+        team_stats.insert((steam_id, team), stats);
+    }
+    let mut player_stats = HashMap::new();
+    for row in read_rows(connection, "players", round, "steam_id", |row| {
+        let steam_id = get::<i64>(row, "steam_id")?;
+        let mut team = |team: Team| {
+            team_stats
+                .remove(&(steam_id, team_to_sql(team)))
+                .ok_or_else(|| rusqlite::Error::QueryReturnedNoRows)
+        };
+        let player = PlayerStat {
+            marines: team(Team::Marines)?,
+            aliens: team(Team::Aliens)?,
+            is_rookie: get(row, "is_rookie")?,
+            weapons: HashMap::new(),
+            status: Vec::new(),
+            last_team: team_from_sql(row, "last_team")?,
+            hive_skill: get(row, "hive_skill")?,
+            player_name: get(row, "player_name")?,
+            commander_skill_offset: get(row, "commander_skill_offset")?,
+            commander_skill: get(row, "commander_skill")?,
+            player_skill_offset: get(row, "player_skill_offset")?,
+        };
+        Ok((steam_id, player))
+    })? {
+        player_stats.insert(row.0, row.1);
+    }
+    for (steam_id, status) in read_rows(connection, "player_classes", round, "steam_id, position", |row| {
+        let status = Status {
+            status_id: name_from_sql(row, "class")?,
+            class_time: get(row, "class_time")?,
+        };
+        Ok((get::<i64>(row, "steam_id")?, status))
+    })? {
+        if let Some(player) = player_stats.get_mut(&steam_id) {
+            player.status.push(status);
+        }
+    }
+    for (steam_id, name, weapon) in read_rows(connection, "weapons", round, "steam_id, weapon", |row| {
+        let weapon = Weapon {
+            team: team_from_sql(row, "team")?,
+            kills: get(row, "kills")?,
+            onos_hits: get(row, "onos_hits")?,
+            player_damage: get(row, "player_damage")?,
+            hits: get(row, "hits")?,
+            structure_damage: get(row, "structure_damage")?,
+            misses: get(row, "misses")?,
+        };
+        Ok((get::<i64>(row, "steam_id")?, get::<String>(row, "weapon")?, weapon))
+    })? {
+        if let Some(player) = player_stats.get_mut(&steam_id) {
+            player.weapons.insert(name, weapon);
+        }
+    }
+
+    let kill_feed = read_rows(connection, "kills", round, "position", |row| {
+        Ok(KillFeed {
+            killer_weapon: get(row, "killer_weapon")?,
+            killer_steam_id: get(row, "killer_steam_id")?,
+            killer_location: get(row, "killer_location")?,
+            killer_position: position_from_sql(row, "killer_")?,
+            killer_class: optional_name_from_sql(row, "killer_class")?,
+            doer_location: get(row, "doer_location")?,
+            doer_position: position_from_sql(row, "doer_")?,
+            killer_team: team_from_sql(row, "killer_team")?,
+            victim_location: get(row, "victim_location")?,
+            victim_steam_id: get(row, "victim_steam_id")?,
+            victim_class: name_from_sql(row, "victim_class")?,
+            victim_position: position_from_sql(row, "victim_")?.unwrap_or(Position { x: 0.0, y: 0.0, z: 0.0 }),
+            game_time: get(row, "game_time")?,
+        })
+    })?;
+    let buildings = read_rows(connection, "buildings", round, "position", |row| {
+        Ok(Building {
+            team: team_from_sql(row, "team")?,
+            game_time: get(row, "game_time")?,
+            built: get(row, "built")?,
+            location: position_from_sql(row, "")?.unwrap_or(Position { x: 0.0, y: 0.0, z: 0.0 }),
+            recycled: get(row, "recycled")?,
+            destroyed: get(row, "destroyed")?,
+            tech_id: get(row, "tech_id")?,
+            biomass: get(row, "biomass")?,
+            entity_id: get(row, "entity_id")?,
+            event: optional_name_from_sql(row, "event")?,
+        })
+    })?;
+    let research = read_rows(connection, "research", round, "position", |row| {
+        Ok(Research {
+            team: team_from_sql(row, "team")?,
+            game_time: get(row, "game_time")?,
+            research_id: get(row, "research_id")?,
+        })
+    })?;
+    let marine_comm_stats = read_rows(connection, "marine_comm_stats", round, "steam_id", |row| {
+        let stats = MarineCommStat {
+            medpack: Medpack {
+                picks: get(row, "medpack_picks")?,
+                misses: get(row, "medpack_misses")?,
+                refilled: get(row, "medpack_refilled")?,
+                hits_acc: get(row, "medpack_hits_acc")?,
+            },
+            ammopack: Ammopack {
+                picks: get(row, "ammopack_picks")?,
+                misses: get(row, "ammopack_misses")?,
+                refilled: get(row, "ammopack_refilled")?,
+            },
+            catpack: Catpack {
+                picks: get(row, "catpack_picks")?,
+                misses: get(row, "catpack_misses")?,
+            },
+        };
+        Ok((get::<String>(row, "steam_id")?, stats))
+    })?
+    .into_iter()
+    .collect();
+
+    Ok(GameStats {
+        kill_feed,
+        locations,
+        research,
+        buildings,
+        player_stats,
+        round_info,
+        server_info,
+        marine_comm_stats,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load;
+
+    #[test]
+    fn games_roundtrip() {
+        let games = load::load("../test_data").unwrap().games;
+        let mut database = Database::open_in_memory().unwrap();
+        assert_eq!(database.insert_games(&games).unwrap(), games.len());
+        // storing them again doesn't add anything
+        assert_eq!(database.insert_games(&games).unwrap(), 0);
+
+        let (id, game) = games.iter().next().unwrap();
+        let stored = database.game(id).unwrap().unwrap();
+        // -0 and 0 are equal as floats, but not when serialized
+        assert_eq!(stored, *game);
+
+        let filter = GameFilter {
+            map: Some(game.round_info.map_name.clone()),
+            winner: Some(crate::WinningTeam::Aliens),
+            ..GameFilter::default()
+        };
+        let expected = games
+            .iter()
+            .filter(|(_, game)| filter.matches(game))
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<_>>();
+        let stored = database.games(&filter).unwrap().into_iter().map(|(id, _)| id).collect::<Vec<_>>();
+        assert!(!expected.is_empty());
+        assert_eq!(stored, expected);
+
+        // the stats are the same as when they are computed in memory
+        let stats = database.stats(&GameFilter::genuine()).unwrap();
+        let expected = NS2Stats::compute(games.values().filter(|game| game.is_genuine()));
+        assert_eq!(stats.total_games, expected.total_games);
+        assert_eq!(stats.marine_wins, expected.marine_wins);
+        assert_eq!(stats.users.len(), expected.users.len());
+        for (name, user) in &expected.users {
+            let stored = &stats.users[name];
+            assert_eq!(
+                (stored.games.total, stored.kills.total, stored.score.total, stored.commander.total),
+                (user.games.total, user.kills.total, user.score.total, user.commander.total)
+            );
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use serde::Deserialize;
+
+use crate::input_types::GameStats;
+use crate::WinningTeam;
+
+/// Criteria for selecting games. All criteria that are set have to match.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct GameFilter {
+    /// The earliest round date in Unix time.
+    pub from: Option<u32>,
+    /// The latest round date in Unix time.
+    pub to: Option<u32>,
+    /// The map name.
+    pub map: Option<String>,
+    /// The name of a player that took part in the game.
+    pub player: Option<String>,
+    /// The server IP, `<ip>:<port>` or the server name.
+    pub server: Option<String>,
+    /// The minimum round length in seconds.
+    pub min_length: Option<f32>,
+    /// The team that won, [`WinningTeam::None`] for games without a winner.
+    pub winner: Option<WinningTeam>,
+    /// Only include genuine games, see [`GameStats::is_genuine`].
+    #[serde(default)]
+    pub genuine: bool,
+}
+
+impl GameFilter {
+    /// All genuine games, which are the games the stats are computed from.
+    pub fn genuine() -> Self {
+        Self {
+            genuine: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn matches(&self, game: &GameStats) -> bool {
+        let round_info = &game.round_info;
+        let server_info = &game.server_info;
+        (!self.genuine || game.is_genuine())
+            && self.from.is_none_or(|from| round_info.round_date >= from)
+            && self.to.is_none_or(|to| round_info.round_date <= to)
+            && self.map.as_ref().is_none_or(|map| &round_info.map_name == map)
+            && self
+                .player
+                .as_ref()
+                .is_none_or(|player| game.player_stats.values().any(|player_stat| &player_stat.player_name == player))
+            && self
+                .server
+                .as_ref()
+                .is_none_or(|server| server == &server_info.ip || server == &server_info.name || *server == format!("{}:{}", server_info.ip, server_info.port))
+            && self.min_length.is_none_or(|min_length| round_info.round_length >= min_length)
+            && self.winner.is_none_or(|winner| WinningTeam::from(round_info.winning_team) == winner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn winner_in_both_spellings() {
+        for winner in ["marines", "Marines"] {
+            let filter = serde_json::from_value::<GameFilter>(serde_json::json!({ "winner": winner })).unwrap();
+            assert_eq!(filter.winner, Some(WinningTeam::Marines));
+        }
+        assert!(serde_json::from_value::<GameFilter>(serde_json::json!({ "winner": "MARINES" })).is_err());
+    }
+    #[test]
+    fn all_games_unless_genuine() {
+        let games = crate::load::load("../test_data").unwrap().games;
+        let filter = serde_json::from_value::<GameFilter>(serde_json::json!({})).unwrap();
+        assert!(games.values().all(|game| filter.matches(game)));
+        let filter = serde_json::from_value::<GameFilter>(serde_json::json!({ "genuine": true })).unwrap();
+        assert!(games.values().any(|game| !filter.matches(game)));
+        assert!(games.values().all(|game| filter.matches(game) == game.is_genuine()));
+    }
+}
@@ -0,0 +1,136 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::de::Visitor;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::input_types::GameStats;
+
+/// Identifies a game by the round date, the server it was played on and the file it was loaded from.
+///
+/// The round date alone is not unique, since two servers can finish a round in the same second.
+/// The file name distinguishes conflicting files that claim to be the same round on the same server.
+///
+/// Identifiers are ordered by the round date first, so a sorted collection of games is in chronological order.
+/// The string representation is `<round date>-<server ip>-<server port>-<file name>`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GameId {
+    /// The round date in Unix time.
+    pub round_date: u32,
+    pub server_ip: String,
+    pub server_port: u16,
+    /// The name of the file the game was loaded from, without the directory.
+    pub file_name: String,
+}
+
+impl GameId {
+    pub fn new<P: AsRef<Path>>(game: &GameStats, path: P) -> Self {
+        Self {
+            round_date: game.round_info.round_date,
+            server_ip: game.server_info.ip.clone(),
+            server_port: game.server_info.port,
+            file_name: path.as_ref().file_name().unwrap_or_default().to_string_lossy().into_owned(),
+        }
+    }
+
+    /// The smallest identifier with the given round date.
+    pub fn first_of(round_date: u32) -> Self {
+        Self {
+            round_date,
+            server_ip: String::new(),
+            server_port: 0,
+            file_name: String::new(),
+        }
+    }
+
+    /// Returns `true` if both identifiers refer to the same round on the same server.
+    pub fn same_round(&self, other: &GameId) -> bool {
+        self.round_date == other.round_date && self.server_ip == other.server_ip && self.server_port == other.server_port
+    }
+}
+
+impl fmt::Display for GameId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}-{}-{}", self.round_date, self.server_ip, self.server_port, self.file_name)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseGameIdError;
+
+impl fmt::Display for ParseGameIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid game id, expected `<round date>-<server ip>-<server port>-<file name>`")
+    }
+}
+
+impl std::error::Error for ParseGameIdError {}
+
+impl FromStr for GameId {
+    type Err = ParseGameIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut splits = s.splitn(4, '-');
+        let mut next = || splits.next().ok_or(ParseGameIdError);
+        Ok(Self {
+            round_date: next()?.parse().map_err(|_| ParseGameIdError)?,
+            server_ip: next()?.to_owned(),
+            server_port: next()?.parse().map_err(|_| ParseGameIdError)?,
+            file_name: next()?.to_owned(),
+        })
+    }
+}
+
+impl Serialize for GameId {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for GameId {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct GameIdVisitor {}
+
+        impl<'de> Visitor<'de> for GameIdVisitor {
+            type Value = GameId;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a game id")
+            }
+
+            fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                s.parse().map_err(|_| serde::de::Error::invalid_value(serde::de::Unexpected::Str(s), &self))
+            }
+        }
+
+        deserializer.deserialize_str(GameIdVisitor {})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn game_id_roundtrip() {
+        let id = GameId {
+            round_date: 1642714057,
+            server_ip: "77.179.12.31".to_owned(),
+            server_port: 27015,
+            file_name: "1642714057-backup.json".to_owned(),
+        };
+        assert_eq!(id.to_string(), "1642714057-77.179.12.31-27015-1642714057-backup.json");
+        assert_eq!(id.to_string().parse::<GameId>(), Ok(id));
+        assert!("1642714057-77.179.12.31".parse::<GameId>().is_err());
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::input_types::{GameStats, SteamId};
+use crate::Stat;
+
+/// The official hive skill values of a player in one game.
+///
+/// The marine and alien values are the hive skill plus and minus the respective offset.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct HiveSkillSample {
+    /// The round date in Unix time.
+    pub round_date: u32,
+    pub skill: Stat<u32>,
+    /// The commander skill, if present.
+    pub commander_skill: Option<Stat<u32>>,
+}
+
+pub(crate) fn apply_offset(skill: u32, offset: Option<i32>) -> Stat<u32> {
+    let offset = offset.unwrap_or(0);
+    Stat {
+        total: skill,
+        marines: skill.saturating_add_signed(offset),
+        aliens: skill.saturating_add_signed(-offset),
+    }
+}
+
+/// The hive skill values of a player over time, in chronological order.
+#[derive(Debug, Default, Serialize)]
+pub struct HiveSkillHistory {
+    /// The name of the player in the latest game, since players can change their name.
+    pub name: String,
+    pub samples: Vec<HiveSkillSample>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HiveSkillSummary {
+    /// The hive skill in the latest game.
+    pub current: Stat<u32>,
+    /// The highest hive skill, computed separately for total, marines and aliens.
+    pub peak: Stat<u32>,
+    /// The change of the hive skill per game over the window, if there are at least two games in it.
+    pub trend: Option<Stat<f32>>,
+    /// The commander skill in the latest game with a commander skill.
+    pub commander_current: Option<Stat<u32>>,
+    pub commander_peak: Option<Stat<u32>>,
+    /// The change of the commander skill per game over the window of games with a commander skill.
+    pub commander_trend: Option<Stat<f32>>,
+}
+
+impl HiveSkillHistory {
+    /// Computes the hive skill histories of all players, by Steam ID.
+    ///
+    /// Games where a player has a hive skill of 0 are ignored, since that means the skill was not available.
+    pub fn compute<'a, I: Iterator<Item = &'a GameStats>>(games: I) -> HashMap<SteamId, HiveSkillHistory> {
+        let mut games = games.collect::<Vec<_>>();
+        games.sort_by_key(|game| game.round_info.round_date);
+        let mut histories = HashMap::new();
+        for game in games {
+            Self::add_game(&mut histories, game);
+        }
+        histories
+    }
+
+    /// Adds the hive skills of a single game to the histories, for games that are not all in memory at once. The games
+    /// have to be added in chronological order.
+    pub fn add_game(histories: &mut HashMap<SteamId, HiveSkillHistory>, game: &GameStats) {
+        for (&steam_id, player_stat) in &game.player_stats {
+            if player_stat.hive_skill == 0 {
+                continue;
+            }
+            let history = histories.entry(steam_id).or_default();
+            history.name.clone_from(&player_stat.player_name);
+            history.samples.push(HiveSkillSample {
+                round_date: game.round_info.round_date,
+                skill: apply_offset(player_stat.hive_skill, player_stat.player_skill_offset),
+                commander_skill: player_stat
+                    .commander_skill
+                    .filter(|&skill| skill > 0)
+                    .map(|skill| apply_offset(skill, player_stat.commander_skill_offset)),
+            });
+        }
+    }
+
+    /// The histories by the latest name of the players, to look them up with the stats, which are by name. If players
+    /// share their latest name, the one who played last is kept.
+    pub fn by_name(histories: HashMap<SteamId, HiveSkillHistory>) -> HashMap<String, HiveSkillHistory> {
+        let mut by_name = HashMap::<String, HiveSkillHistory>::new();
+        for history in histories.into_values() {
+            let last_played = |history: &HiveSkillHistory| history.samples.last().map(|sample| sample.round_date);
+            match by_name.get(&history.name) {
+                Some(other) if last_played(other) > last_played(&history) => {}
+                _ => {
+                    by_name.insert(history.name.clone(), history);
+                }
+            }
+        }
+        by_name
+    }
+
+    fn commander_skills(&self) -> Vec<Stat<u32>> {
+        self.samples.iter().filter_map(|sample| sample.commander_skill).collect()
+    }
+
+    /// The hive skill in the latest game.
+    pub fn current(&self) -> Option<Stat<u32>> {
+        self.samples.last().map(|sample| sample.skill)
+    }
+
+    /// The highest hive skill, computed separately for total, marines and aliens.
+    pub fn peak(&self) -> Option<Stat<u32>> {
+        peak(self.samples.iter().map(|sample| sample.skill))
+    }
+
+    /// The change of the hive skill per game over the last `window` games, computed by linear regression.
+    pub fn trend(&self, window: usize) -> Option<Stat<f32>> {
+        trend(&self.samples.iter().map(|sample| sample.skill).collect::<Vec<_>>(), window)
+    }
+
+    /// The commander skill in the latest game with a commander skill.
+    pub fn commander_current(&self) -> Option<Stat<u32>> {
+        self.samples.iter().rev().find_map(|sample| sample.commander_skill)
+    }
+
+    /// Like [`peak`](Self::peak), for the commander skill.
+    pub fn commander_peak(&self) -> Option<Stat<u32>> {
+        peak(self.commander_skills().into_iter())
+    }
+
+    /// Like [`trend`](Self::trend), for the commander skill over the last `window` games with a commander skill.
+    pub fn commander_trend(&self, window: usize) -> Option<Stat<f32>> {
+        trend(&self.commander_skills(), window)
+    }
+
+    pub fn summary(&self, window: usize) -> Option<HiveSkillSummary> {
+        Some(HiveSkillSummary {
+            current: self.current()?,
+            peak: self.peak()?,
+            trend: self.trend(window),
+            commander_current: self.commander_current(),
+            commander_peak: self.commander_peak(),
+            commander_trend: self.commander_trend(window),
+        })
+    }
+}
+
+fn peak(skills: impl Iterator<Item = Stat<u32>>) -> Option<Stat<u32>> {
+    skills.reduce(|peak, skill| Stat::map([peak, skill], |[peak, skill]| peak.max(skill)))
+}
+
+fn trend(skills: &[Stat<u32>], window: usize) -> Option<Stat<f32>> {
+    let skills = &skills[skills.len().saturating_sub(window)..];
+    if skills.len() < 2 {
+        return None;
+    }
+    let n = skills.len() as f32;
+    let mean_x = (n - 1.0) / 2.0;
+    let slope = |skill: fn(&Stat<u32>) -> u32| {
+        let mean_y = skills.iter().map(|stat| skill(stat) as f32).sum::<f32>() / n;
+        let (mut covariance, mut variance) = (0.0, 0.0);
+        for (x, stat) in skills.iter().enumerate() {
+            let dx = x as f32 - mean_x;
+            covariance += dx * (skill(stat) as f32 - mean_y);
+            variance += dx * dx;
+        }
+        covariance / variance
+    };
+    Some(Stat {
+        total: slope(|skill| skill.total),
+        marines: slope(|skill| skill.marines),
+        aliens: slope(|skill| skill.aliens),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(skill: u32, commander_skill: Option<u32>) -> HiveSkillSample {
+        HiveSkillSample {
+            round_date: 0,
+            skill: apply_offset(skill, Some(10)),
+            commander_skill: commander_skill.map(|skill| apply_offset(skill, None)),
+        }
+    }
+
+    #[test]
+    fn hive_skill_trend() {
+        let history = HiveSkillHistory {
+            name: "player".to_owned(),
+            samples: vec![
+                sample(1000, Some(800)),
+                sample(1300, None),
+                sample(1100, Some(700)),
+                sample(1200, Some(1000)),
+                sample(1300, None),
+            ],
+        };
+        assert_eq!(history.current().map(|skill| skill.marines), Some(1310));
+        assert_eq!(history.peak().map(|skill| skill.aliens), Some(1290));
+        assert_eq!(history.trend(3).map(|trend| trend.total), Some(100.0));
+        assert!(history.trend(1).is_none());
+        assert_eq!(history.commander_current().map(|skill| skill.total), Some(1000));
+        assert_eq!(history.commander_peak().map(|skill| skill.total), Some(1000));
+        assert_eq!(history.commander_trend(2).map(|trend| trend.total), Some(300.0));
+    }
+
+    #[test]
+    fn histories_by_steam_id() {
+        let games = crate::load::load("../test_data").unwrap().games;
+        let histories = HiveSkillHistory::compute(games.values());
+        let (steam_id, game) = games
+            .values()
+            .rev()
+            .find_map(|game| {
+                game.player_stats
+                    .iter()
+                    .find(|(_, player_stat)| player_stat.hive_skill > 0)
+                    .map(|(id, _)| (*id, game))
+            })
+            .unwrap();
+        assert_eq!(histories[&steam_id].name, game.player_stats[&steam_id].player_name);
+        assert!(HiveSkillHistory::by_name(histories).contains_key(&game.player_stats[&steam_id].player_name));
+    }
+}
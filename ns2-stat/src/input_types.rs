@@ -7,11 +7,11 @@ use serde_repr::{Deserialize_repr, Serialize_repr};
 pub type SteamId = i64;
 pub type Location = usize;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "PascalCase")]
 pub struct GameStats {
     pub kill_feed: Vec<KillFeed>,
-    /// A vector with the location names, the locations in other tables are indices into this vector.
+    /// A vector with the location names, the locations in other tables are 1-based indices into this vector.
     pub locations: Vec<String>,
     pub research: Vec<Research>,
     pub buildings: Vec<Building>,
@@ -21,6 +21,13 @@ pub struct GameStats {
     pub marine_comm_stats: HashMap<String, MarineCommStat>,
 }
 
+impl GameStats {
+    /// The name of a location, `None` if it is out of range.
+    pub fn location_name(&self, location: Location) -> Option<&str> {
+        self.locations.get(location.checked_sub(1)?).map(|name| name.as_str())
+    }
+}
+
 impl AsRef<GameStats> for GameStats {
     fn as_ref(&self) -> &GameStats {
         self
@@ -28,7 +35,7 @@ impl AsRef<GameStats> for GameStats {
 }
 
 /// Building completions, deaths and recycles during the game.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Building {
     /// Team that owns the building.
@@ -51,7 +58,7 @@ pub struct Building {
     pub event: Option<Event>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct KillFeed {
     /// Weapon used for the kill.
@@ -83,14 +90,14 @@ pub struct KillFeed {
     pub game_time: f32,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct MarineCommStat {
     pub medpack: Medpack,
     pub ammopack: Ammopack,
     pub catpack: Catpack,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Medpack {
     /// Number of medpacks picked up by players.
@@ -103,7 +110,7 @@ pub struct Medpack {
     pub hits_acc: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Ammopack {
     /// Number of ammopacks picked up by players.
     pub picks: u32,
@@ -113,7 +120,7 @@ pub struct Ammopack {
     pub refilled: f32,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Catpack {
     /// Number of catpacks picked up by players.
     pub picks: u32,
@@ -121,7 +128,7 @@ pub struct Catpack {
     pub misses: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct PlayerStat {
     #[serde(rename = "1")]
@@ -173,10 +180,15 @@ where
         }
     }
 
-    deserializer.deserialize_any(WeaponsVisitor {})
+    // binary formats can't deserialize self-describing data, but they also don't write empty arrays
+    if deserializer.is_human_readable() {
+        deserializer.deserialize_any(WeaponsVisitor {})
+    } else {
+        HashMap::deserialize(deserializer)
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct PlayerTeamStats {
     /// Number of kills.
@@ -207,7 +219,7 @@ pub struct PlayerTeamStats {
     pub structure_damage: f32,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Status {
     /// The class.
@@ -216,7 +228,7 @@ pub struct Status {
     pub class_time: f32,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Weapon {
     #[serde(rename = "teamNumber")]
@@ -230,7 +242,7 @@ pub struct Weapon {
 }
 
 /// Research done during the game.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Research {
     /// Team that owns the research.
@@ -242,7 +254,7 @@ pub struct Research {
     pub research_id: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct RoundInfo {
     /// Unix time for the round.
@@ -266,13 +278,13 @@ pub struct RoundInfo {
     pub map_name: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct MinimapExtents {
     pub origin: String,
     pub scale: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct StartingLocations {
     /// The marine's starting location.
     #[serde(rename = "1")]
@@ -282,7 +294,7 @@ pub struct StartingLocations {
     pub aliens: Location,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct ServerInfo {
     /// The mods active on this server.
@@ -301,14 +313,14 @@ pub struct ServerInfo {
     pub port: u16,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Mod {
     pub mod_id: String,
     pub name: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum Event {
     Built,
     Destroyed,
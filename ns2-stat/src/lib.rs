@@ -1,18 +1,31 @@
 use std::collections::HashMap;
-use std::ops::AddAssign;
+use std::ops::{AddAssign, SubAssign};
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use input_types::{Building, Event, GameStats, PlayerStat, SteamId, Team};
 
+pub use game_id::{GameId, ParseGameIdError};
+
+pub mod anonymize;
+pub mod config;
+#[cfg(feature = "sqlite")]
+pub mod db;
+pub mod filter;
+mod game_id;
+pub mod hive_skill;
 pub mod input_types;
+pub mod load;
+pub mod player;
+pub mod prediction;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
 /// An extension trait for `Iterator` that adds functions related to `GameStats`.
 pub trait GameIterator<G: AsRef<GameStats>>: Iterator<Item = G> where Self: Sized {
-    /// Filter the genuine games. This is done by ignoring games that took under 5 minutes
-    /// and games that were likely bot games.
+    /// Filter the genuine games. See [`GameStats::is_genuine`].
     fn genuine(self) -> impl Iterator<Item = G> {
-        self.filter_by_length(|length| length >= 300.0).filter_bot_games()
+        self.filter(|game| game.as_ref().is_genuine())
     }
 
     /// Filter games with a predicate that takes the length of each game.
@@ -22,26 +35,37 @@ pub trait GameIterator<G: AsRef<GameStats>>: Iterator<Item = G> where Self: Size
 
     /// Ignore games that were likely bot games.
     fn filter_bot_games(self) -> impl Iterator<Item = G> {
-        self.filter(move |game| {
-            let mut max_marines = 0;
-            let mut max_aliens = 0;
-            for player in game.as_ref().player_stats.values() {
-                if player.marines.time_played > 0.0 {
-                    max_marines += 1;
-                }
-                if player.aliens.time_played > 0.0 {
-                    max_aliens += 1;
-                }
-            }
-            max_marines > 2 && max_aliens > 2
-        })
+        self.filter(|game| !game.as_ref().is_bot_game())
     }
 }
 
 impl<G: AsRef<GameStats>, I: Iterator<Item = G>> GameIterator<G> for I {}
 
+impl GameStats {
+    /// Checks if the game is genuine. This is done by ignoring games that took under 5 minutes
+    /// and games that were likely bot games.
+    pub fn is_genuine(&self) -> bool {
+        self.round_info.round_length >= 300.0 && !self.is_bot_game()
+    }
+
+    /// Checks if the game was likely a bot game.
+    pub fn is_bot_game(&self) -> bool {
+        let mut max_marines = 0;
+        let mut max_aliens = 0;
+        for player in self.player_stats.values() {
+            if player.marines.time_played > 0.0 {
+                max_marines += 1;
+            }
+            if player.aliens.time_played > 0.0 {
+                max_aliens += 1;
+            }
+        }
+        max_marines <= 2 || max_aliens <= 2
+    }
+}
+
 // can be used for games, commander, wins, kills, deaths, assists
-#[derive(Clone, Copy, Default, Serialize)]
+#[derive(Clone, Copy, Debug, Default, Serialize)]
 pub struct Stat<T> {
     pub total: T,
     pub marines: T,
@@ -58,6 +82,25 @@ impl<T: AddAssign + Copy> Stat<T> {
     }
 }
 
+impl<T: AddAssign + SubAssign + Copy> Stat<T> {
+    fn sub(&mut self, team: Team, n: T) {
+        self.total -= n;
+        match team {
+            Team::Aliens => self.aliens -= n,
+            Team::Marines => self.marines -= n,
+        }
+    }
+
+    /// Adds `n`, or subtracts it if not `added`.
+    fn update(&mut self, team: Team, n: T, added: bool) {
+        if added {
+            self.add(team, n);
+        } else {
+            self.sub(team, n);
+        }
+    }
+}
+
 impl<T: Copy> Stat<T> {
     fn map<const N: usize, U>(stats: [Stat<T>; N], f: impl Fn([T; N]) -> U) -> Stat<U> {
         Stat {
@@ -68,7 +111,7 @@ impl<T: Copy> Stat<T> {
     }
 }
 
-#[derive(Default, Serialize)]
+#[derive(Clone, Default, Serialize)]
 pub struct User {
     /// The number of games played.
     pub games: Stat<u32>,
@@ -101,16 +144,21 @@ impl User {
     pub fn accuracy(&self) -> Stat<f32> {
         Stat::map([self.hits, self.misses], |[hits, misses]| hits as f32 / (hits + misses) as f32)
     }
+
+    /// `wins / games`
+    pub fn win_rate(&self) -> Stat<f32> {
+        Stat::map([self.wins, self.games], |[wins, games]| wins as f32 / games as f32)
+    }
 }
 
-#[derive(Default, Serialize)]
+#[derive(Clone, Default, Serialize)]
 pub struct Map {
     pub total_games: u32,
     pub marine_wins: u32,
     pub alien_wins: u32,
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Default, Serialize)]
 pub struct NS2Stats {
     pub latest_game: u32,
     pub users: HashMap<String, User>,
@@ -122,83 +170,97 @@ pub struct NS2Stats {
 
 impl NS2Stats {
     pub fn compute<'a, I: Iterator<Item = &'a GameStats>>(games: I) -> Self {
-        use input_types::WinningTeam;
+        let mut stats = Self::default();
+        for game in games {
+            stats.add_game(game);
+        }
+        stats
+    }
 
-        let mut users = HashMap::new();
-        let mut maps = HashMap::new();
-        let mut marine_wins = 0;
-        let mut alien_wins = 0;
-        let mut total_games = 0;
-        let mut latest_game = 0;
+    /// Adds a single game to the stats, for games that are not all in memory at once.
+    pub fn add_game(&mut self, game: &GameStats) {
+        self.count_game(game, true);
+        if game.round_info.round_date > self.latest_game {
+            self.latest_game = game.round_info.round_date;
+        }
+    }
 
-        for game in games {
-            for player_stat in game.player_stats.values() {
-                let user = match users.get_mut(&player_stat.player_name) {
-                    Some(user) => user,
-                    None => users.entry(player_stat.player_name.clone()).or_insert_with(User::default),
-                };
-
-                let (team, stats) = if player_stat.marines.time_played > player_stat.aliens.time_played {
-                    // player was in marine team
-                    if game.round_info.winning_team == WinningTeam::Marines {
-                        user.wins.add(Team::Marines, 1);
-                    }
-                    (Team::Marines, &player_stat.marines)
-                } else {
-                    // player was in alien team
-                    if game.round_info.winning_team == WinningTeam::Aliens {
-                        user.wins.add(Team::Aliens, 1);
-                    }
-                    (Team::Aliens, &player_stat.aliens)
-                };
-                user.games.add(team, 1);
-                user.kills.add(team, stats.kills);
-                user.assists.add(team, stats.assists);
-                user.deaths.add(team, stats.deaths);
-                user.score.add(team, stats.score as f32 / game.round_info.round_length);
-                user.hits.add(team, stats.hits);
-                user.misses.add(team, stats.misses);
-            }
-            let marine_commander = get_commander(Team::Marines, &game.player_stats).unwrap_or_default();
-            if let Some(user) = users.get_mut(marine_commander) {
-                user.commander.add(Team::Marines, 1);
-            }
-            let alien_commander = get_commander(Team::Aliens, &game.player_stats).unwrap_or_default();
-            if let Some(user) = users.get_mut(alien_commander) {
-                user.commander.add(Team::Aliens, 1);
+    /// Removes a game that was added before from the stats. Players and maps without games are removed as well.
+    ///
+    /// `latest_game` is kept, since the date of the game before is unknown. If it was the latest game, it has to be
+    /// set from the remaining games.
+    pub fn remove_game(&mut self, game: &GameStats) {
+        self.count_game(game, false);
+        self.users.retain(|_, user| user.games.total > 0);
+        self.maps.retain(|_, map| map.total_games > 0);
+    }
+
+    /// Adds the game to the counts, or subtracts it if not `added`.
+    fn count_game(&mut self, game: &GameStats, added: bool) {
+        use input_types::WinningTeam;
+
+        fn count(n: &mut u32, added: bool) {
+            if added {
+                *n += 1;
+            } else {
+                *n -= 1;
             }
+        }
 
-            let map_entry = match maps.get_mut(&game.round_info.map_name) {
-                Some(map) => map,
-                None => maps.entry(game.round_info.map_name.clone()).or_insert_with(Map::default),
+        let users = &mut self.users;
+        for player_stat in game.player_stats.values() {
+            let user = match users.get_mut(&player_stat.player_name) {
+                Some(user) => user,
+                None => users.entry(player_stat.player_name.clone()).or_default(),
             };
-            map_entry.total_games += 1;
-            match game.round_info.winning_team {
-                WinningTeam::Marines => {
-                    map_entry.marine_wins += 1;
-                    marine_wins += 1;
+
+            let (team, stats) = if player_stat.marines.time_played > player_stat.aliens.time_played {
+                // player was in marine team
+                if game.round_info.winning_team == WinningTeam::Marines {
+                    user.wins.update(Team::Marines, 1, added);
                 }
-                WinningTeam::Aliens => {
-                    map_entry.alien_wins += 1;
-                    alien_wins += 1;
+                (Team::Marines, &player_stat.marines)
+            } else {
+                // player was in alien team
+                if game.round_info.winning_team == WinningTeam::Aliens {
+                    user.wins.update(Team::Aliens, 1, added);
                 }
-                WinningTeam::None => {}
-            }
-
-            if game.round_info.round_date > latest_game {
-                latest_game = game.round_info.round_date;
-            }
-            total_games += 1;
+                (Team::Aliens, &player_stat.aliens)
+            };
+            user.games.update(team, 1, added);
+            user.kills.update(team, stats.kills, added);
+            user.assists.update(team, stats.assists, added);
+            user.deaths.update(team, stats.deaths, added);
+            user.score.update(team, stats.score as f32 / game.round_info.round_length, added);
+            user.hits.update(team, stats.hits, added);
+            user.misses.update(team, stats.misses, added);
+        }
+        let marine_commander = get_commander(Team::Marines, &game.player_stats).unwrap_or_default();
+        if let Some(user) = users.get_mut(marine_commander) {
+            user.commander.update(Team::Marines, 1, added);
+        }
+        let alien_commander = get_commander(Team::Aliens, &game.player_stats).unwrap_or_default();
+        if let Some(user) = users.get_mut(alien_commander) {
+            user.commander.update(Team::Aliens, 1, added);
         }
 
-        Self {
-            latest_game,
-            users,
-            maps,
-            total_games,
-            marine_wins,
-            alien_wins,
+        let map_entry = match self.maps.get_mut(&game.round_info.map_name) {
+            Some(map) => map,
+            None => self.maps.entry(game.round_info.map_name.clone()).or_default(),
+        };
+        count(&mut map_entry.total_games, added);
+        match game.round_info.winning_team {
+            WinningTeam::Marines => {
+                count(&mut map_entry.marine_wins, added);
+                count(&mut self.marine_wins, added);
+            }
+            WinningTeam::Aliens => {
+                count(&mut map_entry.alien_wins, added);
+                count(&mut self.alien_wins, added);
+            }
+            WinningTeam::None => {}
         }
+        count(&mut self.total_games, added);
     }
 }
 
@@ -215,7 +277,7 @@ pub struct PlayerSummary {
 #[derive(Debug, Serialize)]
 pub struct TeamSummary {
     pub players: HashMap<String, PlayerSummary>,
-    /// The name of the commander, if present.
+    /// The name of the player with the most commander time, `None` if nobody commanded.
     pub commander: Option<String>,
     /// The times when the resource tower (RT) amount changed and the amounts it changed to.
     pub rt_graph: Vec<(f32, u32)>,
@@ -227,10 +289,14 @@ impl TeamSummary {
     }
 }
 
-#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+/// Deserializes from the capitalized names, like it is serialized, and from the lowercase names of the CLI.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub enum WinningTeam {
+    #[serde(alias = "none")]
     None,
+    #[serde(alias = "aliens")]
     Aliens,
+    #[serde(alias = "marines")]
     Marines,
 }
 
@@ -244,8 +310,19 @@ impl From<input_types::WinningTeam> for WinningTeam {
     }
 }
 
+impl From<WinningTeam> for input_types::WinningTeam {
+    fn from(value: WinningTeam) -> Self {
+        match value {
+            WinningTeam::None => input_types::WinningTeam::None,
+            WinningTeam::Aliens => input_types::WinningTeam::Aliens,
+            WinningTeam::Marines => input_types::WinningTeam::Marines,
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct GameSummary {
+    pub id: GameId,
     /// The round date in Unix time.
     pub round_date: u32,
     pub winning_team: WinningTeam,
@@ -256,7 +333,7 @@ pub struct GameSummary {
     pub marines: TeamSummary,
 }
 
-pub fn summarize_game(game: &GameStats) -> GameSummary {
+pub fn summarize_game(id: &GameId, game: &GameStats) -> GameSummary {
     let round_info = &game.round_info;
     let mut aliens = HashMap::new();
     let mut marines = HashMap::new();
@@ -279,6 +356,7 @@ pub fn summarize_game(game: &GameStats) -> GameSummary {
         );
     }
     GameSummary {
+        id: id.clone(),
         round_date: round_info.round_date,
         winning_team: round_info.winning_team.into(),
         round_length: round_info.round_length,
@@ -328,13 +406,63 @@ fn compute_rt_graph(team: Team, buildings: &[Building], round_length: f32) -> Ve
 }
 
 fn get_commander(team: Team, player_stats: &HashMap<SteamId, PlayerStat>) -> Option<&str> {
-    match team {
-        Team::Marines => player_stats
-            .values()
-            .max_by_key(|player_stat| (player_stat.marines.commander_time * 1000.0) as u32),
-        Team::Aliens => player_stats
-            .values()
-            .max_by_key(|player_stat| (player_stat.aliens.commander_time * 1000.0) as u32),
+    let commander_time = |player_stat: &PlayerStat| match team {
+        Team::Marines => player_stat.marines.commander_time,
+        Team::Aliens => player_stat.aliens.commander_time,
+    };
+    // nobody commanded if nobody has commander time, rather than an arbitrary player
+    player_stats
+        .values()
+        .filter(|player_stat| commander_time(player_stat) > 0.0)
+        .max_by_key(|player_stat| (commander_time(player_stat) * 1000.0) as u32)
+        .map(|player_stat| &*player_stat.player_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_commander_without_commander_time() {
+        let games = load::load("../test_data").unwrap().games;
+        let (id, game) = games.iter().find(|(id, game)| summarize_game(id, game).marines.commander.is_some()).unwrap();
+        let mut game = game.clone();
+        for player_stat in game.player_stats.values_mut() {
+            player_stat.marines.commander_time = 0.0;
+        }
+        let summary = summarize_game(id, &game);
+        assert_eq!(summary.marines.commander, None);
+        assert_eq!(
+            NS2Stats::compute([&game].into_iter())
+                .users
+                .values()
+                .map(|user| user.commander.marines)
+                .sum::<u32>(),
+            0
+        );
+
+        let player_stat = game.player_stats.values_mut().next().unwrap();
+        player_stat.marines.commander_time = 1.0;
+        let name = player_stat.player_name.clone();
+        assert_eq!(summarize_game(id, &game).marines.commander, Some(name));
+    }
+
+    #[test]
+    fn remove_game_undoes_add_game() {
+        let games = load::load("../test_data").unwrap().games;
+        let mut stats = NS2Stats::compute(games.values().genuine());
+        let (removed, kept): (Vec<_>, Vec<_>) = games.values().genuine().enumerate().partition(|(i, _)| i % 3 == 0);
+        for (_, game) in &removed {
+            stats.remove_game(game);
+        }
+        let expected = NS2Stats::compute(kept.into_iter().map(|(_, game)| game));
+        stats.latest_game = expected.latest_game;
+        // the scores are sums of floats, which can differ in the last digits
+        for (name, user) in &mut stats.users {
+            let score = expected.users[name].score;
+            assert!((user.score.total - score.total).abs() < 1e-3 * score.total.max(1.0));
+            user.score = score;
+        }
+        assert_eq!(serde_json::to_value(&stats).unwrap(), serde_json::to_value(&expected).unwrap());
     }
-    .map(|player_stat| &*player_stat.player_name)
 }
@@ -0,0 +1,982 @@
+//! Loading the game files written by the NS2 server.
+//!
+//! Game files are JSON files, which can be compressed with gzip (`.json.gz`) or zstd (`.json.zst`). They can be in
+//! nested directories and in tar (`.tar`, `.tar.gz`, `.tgz`, `.tar.zst`) or zip (`.zip`) archives. Other files are ignored.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use std::{env, fmt};
+
+use flate2::read::GzDecoder;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::input_types::{GameStats, Location};
+use crate::GameId;
+
+/// An error while loading games or the configuration.
+#[derive(Debug)]
+pub enum LoadError {
+    /// A file, directory or archive could not be read.
+    Io { path: PathBuf, source: io::Error },
+    /// A game file is not valid JSON or doesn't have the expected structure.
+    Parse {
+        path: PathBuf,
+        /// The line of the error in the (decompressed) file, starting at 1.
+        line: usize,
+        /// The column of the error in the line, starting at 1.
+        column: usize,
+        source: serde_json::Error,
+    },
+    /// A game file was parsed, but its content is inconsistent, see [`validate`].
+    Invalid { path: PathBuf, reason: String },
+    /// A configuration file is not valid.
+    Config { path: PathBuf, source: toml::de::Error },
+}
+
+impl LoadError {
+    pub(crate) fn io<E: Into<io::Error>>(path: &Path) -> impl Fn(E) -> Self + Copy + '_ {
+        move |source| Self::Io {
+            path: path.to_owned(),
+            source: source.into(),
+        }
+    }
+
+    fn parse(path: &Path, source: serde_json::Error) -> Self {
+        Self::Parse {
+            path: path.to_owned(),
+            line: source.line(),
+            column: source.column(),
+            source,
+        }
+    }
+
+    /// The file or directory the error is about.
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Io { path, .. } | Self::Parse { path, .. } | Self::Invalid { path, .. } | Self::Config { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, .. } => write!(f, "failed to read `{}`", path.display()),
+            Self::Parse { path, line, column, .. } => write!(f, "failed to parse `{}` at line {}, column {}", path.display(), line, column),
+            Self::Invalid { path, reason } => write!(f, "invalid game `{}`: {}", path.display(), reason),
+            Self::Config { path, .. } => write!(f, "invalid configuration `{}`", path.display()),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Parse { source, .. } => Some(source),
+            Self::Invalid { .. } => None,
+            Self::Config { source, .. } => Some(source),
+        }
+    }
+}
+
+/// Displays an error with its sources, like a [`LoadError`] with the I/O or JSON error that caused it.
+///
+/// The sources are separated by `: `, or with the alternate flag (`{:#}`) written on separate lines.
+pub struct ErrorChain<'a>(pub &'a dyn Error);
+
+impl fmt::Display for ErrorChain<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)?;
+        let separator = if f.alternate() { "\n" } else { ": " };
+        let mut source = self.0.source();
+        while let Some(err) = source {
+            write!(f, "{}{}", separator, err)?;
+            source = err.source();
+        }
+        Ok(())
+    }
+}
+
+/// What to do with files that can't be loaded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnError {
+    /// Stop loading and return the error.
+    #[default]
+    Fail,
+    /// Skip the file and load the others. The errors are collected in [`Games::rejected`].
+    Collect,
+}
+
+/// The decompressed content of a game file.
+pub struct GameFile {
+    /// The path of the file. Files in archives have the path of the archive joined with their path in the archive.
+    pub path: PathBuf,
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Copy)]
+enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Json(Compression),
+    Tar(Compression),
+    Zip,
+}
+
+fn kind(path: &Path) -> Option<Kind> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    if name.ends_with(".tgz") {
+        return Some(Kind::Tar(Compression::Gzip));
+    }
+    if name.ends_with(".zip") {
+        return Some(Kind::Zip);
+    }
+    let (name, compression) = if let Some(name) = name.strip_suffix(".gz") {
+        (name, Compression::Gzip)
+    } else if let Some(name) = name.strip_suffix(".zst") {
+        (name, Compression::Zstd)
+    } else {
+        (&*name, Compression::None)
+    };
+    if name.ends_with(".json") {
+        Some(Kind::Json(compression))
+    } else if name.ends_with(".tar") {
+        Some(Kind::Tar(compression))
+    } else {
+        None
+    }
+}
+
+fn decompress<'a>(reader: &'a mut dyn Read, compression: Compression) -> io::Result<Box<dyn Read + 'a>> {
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(GzDecoder::new(reader)),
+        Compression::Zstd => Box::new(zstd::Decoder::new(reader)?),
+    })
+}
+
+/// Finds the files at `path` that can contain games, sorted by their path.
+fn sources(path: &Path) -> Result<Vec<PathBuf>, LoadError> {
+    fn walk(path: &Path, sources: &mut Vec<PathBuf>) -> Result<(), LoadError> {
+        if path.is_dir() {
+            for entry in fs::read_dir(path).map_err(LoadError::io(path))? {
+                let entry = entry.map_err(LoadError::io(path))?;
+                walk(&entry.path(), sources)?;
+            }
+        } else if kind(path).is_some() {
+            sources.push(path.to_owned());
+        }
+        Ok(())
+    }
+
+    fs::metadata(path).map_err(LoadError::io(path))?;
+    let mut sources = Vec::new();
+    walk(path, &mut sources)?;
+    sources.sort();
+    Ok(sources)
+}
+
+/// Reads the game files in a source, which is one file for JSON files and any number for archives.
+fn read_source(path: &Path) -> Result<Vec<GameFile>, LoadError> {
+    let mut files = Vec::new();
+    if let Some(kind) = kind(path) {
+        let mut file = File::open(path).map_err(LoadError::io(path))?;
+        read_file(path, kind, &mut file, &mut files)?;
+    }
+    Ok(files)
+}
+
+/// Reads all game files at `path`, which can be a directory or a single file, sorted by their path.
+pub fn read_files<P: AsRef<Path>>(path: P) -> Result<Vec<GameFile>, LoadError> {
+    let mut files = Vec::new();
+    for source in sources(path.as_ref())? {
+        files.extend(read_source(&source)?);
+    }
+    files.sort_by(|file1, file2| file1.path.cmp(&file2.path));
+    Ok(files)
+}
+
+fn read_file(path: &Path, kind: Kind, reader: &mut dyn Read, files: &mut Vec<GameFile>) -> Result<(), LoadError> {
+    let error = LoadError::io(path);
+    match kind {
+        Kind::Json(compression) => {
+            let mut data = Vec::new();
+            decompress(reader, compression)
+                .and_then(|mut reader| reader.read_to_end(&mut data))
+                .map_err(error)?;
+            files.push(GameFile { path: path.to_owned(), data });
+        }
+        Kind::Tar(compression) => {
+            let mut archive = tar::Archive::new(decompress(reader, compression).map_err(error)?);
+            for entry in archive.entries().map_err(error)? {
+                let mut entry = entry.map_err(error)?;
+                if !entry.header().entry_type().is_file() {
+                    continue;
+                }
+                let entry_path = path.join(entry.path().map_err(error)?);
+                if let Some(kind) = self::kind(&entry_path) {
+                    read_file(&entry_path, kind, &mut entry, files)?;
+                }
+            }
+        }
+        Kind::Zip => {
+            // zip archives need to be seekable
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).map_err(error)?;
+            let mut archive = zip::ZipArchive::new(io::Cursor::new(data)).map_err(LoadError::io(path))?;
+            for i in 0..archive.len() {
+                let mut entry = archive.by_index(i).map_err(LoadError::io(path))?;
+                let Some(name) = entry.enclosed_name().filter(|_| entry.is_file()) else {
+                    continue;
+                };
+                let entry_path = path.join(name);
+                if let Some(kind) = self::kind(&entry_path) {
+                    read_file(&entry_path, kind, &mut entry, files)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The games loaded by [`load`].
+pub struct Games {
+    pub games: BTreeMap<GameId, GameStats>,
+    /// Files that were skipped, because a loaded file has the same content. The ID is the one of the loaded game.
+    pub duplicates: Vec<(PathBuf, GameId)>,
+    /// Games that claim to be the same round on the same server as another game, but have a different content.
+    /// Both are kept.
+    pub conflicts: Vec<(GameId, GameId)>,
+    /// Files that could not be loaded, sorted by their path. Always empty with [`OnError::Fail`].
+    pub rejected: Vec<LoadError>,
+    /// To update the games later, when only some sources changed.
+    pub sources: Sources,
+}
+
+/// Checks the parts of a parsed game that the JSON structure doesn't: the round length is a valid duration and
+/// all locations are indices into the location names.
+pub fn validate(game: &GameStats) -> Result<(), String> {
+    let round_length = game.round_info.round_length;
+    if !round_length.is_finite() || round_length < 0.0 {
+        return Err(format!("invalid round length {}", round_length));
+    }
+    let check_location = |location: Location, what: &str| match game.location_name(location) {
+        Some(_) => Ok(()),
+        None => Err(format!(
+            "{} location {} is out of range, there are {} locations",
+            what,
+            location,
+            game.locations.len()
+        )),
+    };
+    let starting_locations = &game.round_info.starting_locations;
+    check_location(starting_locations.marines, "marine starting")?;
+    check_location(starting_locations.aliens, "alien starting")?;
+    for kill in &game.kill_feed {
+        for (location, what) in [(kill.killer_location, "killer"), (kill.doer_location, "doer"), (kill.victim_location, "victim")] {
+            if let Some(location) = location {
+                check_location(location, what)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parses and validates a game file.
+pub fn parse(file: &GameFile) -> Result<GameStats, LoadError> {
+    let game = serde_json::from_slice(&file.data).map_err(|e| LoadError::parse(&file.path, e))?;
+    validate(&game).map_err(|reason| LoadError::Invalid {
+        path: file.path.clone(),
+        reason,
+    })?;
+    Ok(game)
+}
+
+/// A hash that is the same with every Rust version, unlike [`DefaultHasher`](std::collections::hash_map::DefaultHasher),
+/// since it is stored in the cache. Files with the same content have the same hash, but the hash alone could collide.
+pub fn stable_hash(data: &[u8]) -> u64 {
+    let digest = Sha256::digest(data);
+    u64::from_le_bytes(digest[..8].try_into().expect("a SHA-256 digest has 32 bytes"))
+}
+
+/// The game among `ids` that claims to be the same round as `id`, see [`GameId::same_round`]. Such a game conflicts
+/// with `id`, unless it is `id` itself.
+pub fn same_round<'a>(ids: &'a BTreeSet<GameId>, id: &GameId) -> Option<&'a GameId> {
+    ids.range(GameId::first_of(id.round_date)..)
+        .take_while(|other| other.round_date == id.round_date)
+        .find(|other| id.same_round(other))
+}
+
+/// A parsed game file, as it is stored in the cache.
+#[derive(Serialize, Deserialize)]
+struct ParsedFile {
+    path: PathBuf,
+    /// The hash of the file content, to find duplicates.
+    hash: u64,
+    game: GameStats,
+}
+
+/// Parses the game files in a source. The files that could not be parsed are returned separately.
+fn parse_source(path: &Path) -> Result<(Vec<ParsedFile>, Vec<LoadError>), LoadError> {
+    let results = read_source(path)?
+        .into_par_iter()
+        .map(|file| {
+            let game = parse(&file)?;
+            Ok(ParsedFile {
+                path: file.path,
+                hash: stable_hash(&file.data),
+                game,
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+    for result in results {
+        match result {
+            Ok(file) => files.push(file),
+            Err(err) => errors.push(err),
+        }
+    }
+    Ok((files, errors))
+}
+
+/// Identifies the version of a source file. If any of these change, the file is parsed again.
+#[derive(PartialEq, Eq, Serialize, Deserialize)]
+struct SourceVersion {
+    size: u64,
+    modified: SystemTime,
+}
+
+impl SourceVersion {
+    fn of(path: &Path) -> Result<Self, LoadError> {
+        let metadata = fs::metadata(path).map_err(LoadError::io(path))?;
+        Ok(Self {
+            size: metadata.len(),
+            modified: metadata.modified().map_err(LoadError::io(path))?,
+        })
+    }
+}
+
+/// Incremented whenever the layout of the cache or [`GameStats`] changes, so old caches are not misread.
+const CACHE_FORMAT: u32 = 2;
+
+/// Files modified this shortly before the cache was written are parsed again, because they could have been changed
+/// again within the resolution of the modification time, without changing it.
+const MODIFIED_RESOLUTION: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Deserialize)]
+struct Cache {
+    format: u32,
+    /// The version of this crate, in case `CACHE_FORMAT` was not incremented.
+    crate_version: String,
+    /// When the sources were read, before the first of them.
+    read: SystemTime,
+    sources: HashMap<PathBuf, (SourceVersion, Vec<ParsedFile>)>,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self {
+            format: CACHE_FORMAT,
+            crate_version: env!("CARGO_PKG_VERSION").to_owned(),
+            read: SystemTime::UNIX_EPOCH,
+            sources: HashMap::new(),
+        }
+    }
+}
+
+impl Cache {
+    /// Reads the cache, or returns an empty cache if it doesn't exist, is corrupt or from another version.
+    fn read(path: &Path) -> Self {
+        let cache = fs::read(path).ok().and_then(|data| bincode::deserialize::<Cache>(&data).ok());
+        cache
+            .filter(|cache| cache.format == CACHE_FORMAT && cache.crate_version == env!("CARGO_PKG_VERSION"))
+            .unwrap_or_default()
+    }
+
+    /// Takes the parsed files of `source` out of the cache, if the source didn't change since.
+    fn take(&mut self, source: &Path, version: &SourceVersion) -> Option<Vec<ParsedFile>> {
+        let (cached_version, files) = self.sources.remove(source)?;
+        let settled = version.modified + MODIFIED_RESOLUTION < self.read;
+        (cached_version == *version && settled).then_some(files)
+    }
+
+    fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let data = bincode::serialize(self).map_err(io::Error::other)?;
+        // write to a temporary file first, so a concurrent reader never sees a partial cache
+        let temp_path = path.with_extension(format!("tmp{}", std::process::id()));
+        fs::write(&temp_path, data)?;
+        fs::rename(temp_path, path)
+    }
+}
+
+/// The default location of the cache for the games at `data_path`, in the cache directory of the user.
+/// `None` if there is no cache directory.
+pub fn default_cache_path<P: AsRef<Path>>(data_path: P) -> Option<PathBuf> {
+    let cache_dir = match env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None if cfg!(windows) => PathBuf::from(env::var_os("LOCALAPPDATA")?),
+        None => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+    };
+    let data_path = fs::canonicalize(data_path.as_ref()).unwrap_or_else(|_| data_path.as_ref().to_owned());
+    let hash = stable_hash(data_path.as_os_str().as_encoded_bytes());
+    Some(cache_dir.join("ns2-stat").join(format!("{:016x}.bin", hash)))
+}
+
+/// How [`load_with`] loads the games.
+#[derive(Clone, Debug, Default)]
+pub struct LoadOptions {
+    /// The cache file for the parsed games, so only new and changed files are parsed.
+    pub cache: Option<PathBuf>,
+    pub on_error: OnError,
+}
+
+/// Loads all games at `path`, which can be a directory or a single file, and fails at the first file that can't
+/// be loaded.
+///
+/// Files with the same content as an already loaded file are skipped. Of the duplicates, the file with the smallest
+/// path is kept, so it is the same file on every load.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Games, LoadError> {
+    load_with(path, &LoadOptions::default())
+}
+
+/// Like [`load`], but keeps the parsed games in a cache file at `cache_path`, so only new and changed files are parsed.
+pub fn load_cached<P: AsRef<Path>, C: AsRef<Path>>(path: P, cache_path: C) -> Result<Games, LoadError> {
+    load_with(
+        path,
+        &LoadOptions {
+            cache: Some(cache_path.as_ref().to_owned()),
+            ..LoadOptions::default()
+        },
+    )
+}
+
+/// Loads all games at `path` like [`load`], with the cache and error handling of `options`.
+///
+/// With a cache, a file counts as changed if its size or modification time changed. Problems with the cache itself
+/// are not errors, the games are just parsed again. Files that could not be loaded are not cached, so they are tried
+/// again on the next load. A missing `path` is always an error.
+pub fn load_with<P: AsRef<Path>>(path: P, options: &LoadOptions) -> Result<Games, LoadError> {
+    let cache_path = options.cache.as_deref();
+    let mut cache = cache_path.map(Cache::read).unwrap_or_default();
+    let read = SystemTime::now();
+    let sources = sources(path.as_ref())?;
+    let cached_sources = cache.sources.len();
+
+    let sources = sources
+        .into_iter()
+        .map(|source| {
+            let cached = SourceVersion::of(&source)
+                .ok()
+                .and_then(|version| cache.take(&source, &version).map(|files| (version, files)));
+            (source, cached)
+        })
+        .collect::<Vec<_>>();
+    let unchanged = sources.iter().filter(|(_, cached)| cached.is_some()).count();
+    let sources = sources
+        .into_par_iter()
+        .map(|(source, cached)| {
+            let parsed = match cached {
+                Some((version, files)) => Ok((version, files, Vec::new())),
+                None => SourceVersion::of(&source).and_then(|version| {
+                    let (files, errors) = parse_source(&source)?;
+                    Ok((version, files, errors))
+                }),
+            };
+            (source, parsed)
+        })
+        .collect::<Vec<_>>();
+
+    let mut cache = Cache { read, ..Cache::default() };
+    let mut uncached = Vec::new();
+    let mut rejected = Vec::new();
+    for (source, parsed) in sources {
+        match parsed {
+            Ok((version, files, errors)) if errors.is_empty() => {
+                cache.sources.insert(source, (version, files));
+            }
+            Ok((_, files, errors)) => {
+                uncached.push((source, files));
+                rejected.extend(errors);
+            }
+            Err(err) => rejected.push(err),
+        }
+    }
+    if let Some(cache_path) = cache_path.filter(|_| unchanged < cache.sources.len() || unchanged < cached_sources) {
+        // the games are still loaded if the cache can't be written
+        let _ = cache.write(cache_path);
+    }
+    rejected.sort_by(|error1, error2| error1.path().cmp(error2.path()));
+    if options.on_error == OnError::Fail && !rejected.is_empty() {
+        return Err(rejected.swap_remove(0));
+    }
+
+    let mut index = Sources::default();
+    let cached = cache.sources.into_iter().map(|(source, (version, files))| (source, Some(version), files));
+    let uncached = uncached.into_iter().map(|(source, files)| (source, None, files));
+    let mut files = Vec::new();
+    for (source, version, source_files) in cached.chain(uncached) {
+        files.extend(source_files.into_iter().map(|file| (source.clone(), file)));
+        index.sources.insert(
+            source,
+            LoadedSource {
+                version,
+                ..LoadedSource::default()
+            },
+        );
+    }
+    files.sort_by(|(_, file1), (_, file2)| file1.path.cmp(&file2.path));
+    let mut games = Games {
+        games: BTreeMap::new(),
+        duplicates: Vec::new(),
+        conflicts: Vec::new(),
+        rejected,
+        sources: Sources::default(),
+    };
+    let mut contents = Contents::default();
+    for (source, ParsedFile { path, hash, game }) in files {
+        match index.insert(&source, &path, hash, &game, &mut contents) {
+            Inserted::Duplicate(original) => games.duplicates.push((path, original)),
+            Inserted::Game { id, conflict } => {
+                if let Some(conflict) = conflict {
+                    games.conflicts.push((id.clone(), conflict));
+                }
+                games.games.insert(id, game);
+            }
+        }
+    }
+    games.sources = index;
+    Ok(games)
+}
+
+/// The content of game files, read again to compare files with the same hash. Each source is read only once.
+#[derive(Default)]
+struct Contents {
+    sources: HashMap<PathBuf, Vec<GameFile>>,
+}
+
+impl Contents {
+    /// `None` if the file can't be read anymore.
+    fn get(&mut self, source: &Path, path: &Path) -> Option<&[u8]> {
+        let files = self.sources.entry(source.to_owned()).or_insert_with(|| read_source(source).unwrap_or_default());
+        files.iter().find(|file| file.path == path).map(|file| &file.data[..])
+    }
+}
+
+/// Which games were loaded from which source, so the games can be updated when single sources change, see
+/// [`Sources::update`].
+#[derive(Default)]
+pub struct Sources {
+    sources: HashMap<PathBuf, LoadedSource>,
+    /// The loaded files by content hash, to skip duplicates. There can be multiple files with the same hash if they
+    /// differ.
+    hashes: HashMap<u64, Vec<LoadedFile>>,
+    /// The sources of the skipped duplicates by content hash, to load them when the loaded file is removed.
+    skipped: HashMap<u64, Vec<PathBuf>>,
+    /// The IDs of all loaded games, to find conflicts.
+    ids: BTreeSet<GameId>,
+}
+
+struct LoadedFile {
+    id: GameId,
+    source: PathBuf,
+    path: PathBuf,
+}
+
+#[derive(Default)]
+struct LoadedSource {
+    /// `None` for sources with errors, so they are parsed again on every update.
+    version: Option<SourceVersion>,
+    /// The loaded games with the hash of their file content.
+    games: Vec<(GameId, u64)>,
+    /// The content hashes of the skipped duplicates.
+    skipped: Vec<u64>,
+}
+
+/// The result of [`Sources::insert`].
+enum Inserted {
+    /// The file was skipped, because the game with this ID has the same content.
+    Duplicate(GameId),
+    /// The game was added, and it conflicts with another game if it claims to be the same round.
+    Game { id: GameId, conflict: Option<GameId> },
+}
+
+/// The changes of [`Sources::update`].
+#[derive(Default)]
+pub struct Update {
+    /// The games of new and changed sources.
+    pub added: Vec<(GameId, GameStats)>,
+    /// The games of changed and removed sources. A changed game can be in both lists with the same ID, so these
+    /// have to be removed before the added games are inserted.
+    pub removed: Vec<GameId>,
+    /// The sources that were parsed again or removed.
+    pub updated: Vec<PathBuf>,
+    /// Files that were skipped, like [`Games::duplicates`].
+    pub duplicates: Vec<(PathBuf, GameId)>,
+    /// Added games that claim to be the same round as another game, like [`Games::conflicts`].
+    pub conflicts: Vec<(GameId, GameId)>,
+    /// The errors of the sources that could not be loaded completely, by source.
+    pub rejected: Vec<(PathBuf, Vec<LoadError>)>,
+}
+
+impl Sources {
+    /// Adds a parsed file of `source`, unless a loaded file has the same content.
+    fn insert(&mut self, source: &Path, path: &Path, hash: u64, game: &GameStats, contents: &mut Contents) -> Inserted {
+        let original = self.identical(hash, source, path, contents);
+        let loaded_source = self.sources.entry(source.to_owned()).or_default();
+        if let Some(original) = original {
+            loaded_source.skipped.push(hash);
+            self.skipped.entry(hash).or_default().push(source.to_owned());
+            return Inserted::Duplicate(original);
+        }
+        let id = GameId::new(game, path);
+        loaded_source.games.push((id.clone(), hash));
+        self.hashes.entry(hash).or_default().push(LoadedFile {
+            id: id.clone(),
+            source: source.to_owned(),
+            path: path.to_owned(),
+        });
+        let conflict = same_round(&self.ids, &id).cloned();
+        self.ids.insert(id.clone());
+        Inserted::Game { id, conflict }
+    }
+
+    /// Removes the games of `source`. Returns the sources with skipped duplicates of the removed games, which have to
+    /// be parsed again to load the duplicates instead.
+    fn remove(&mut self, source: &Path, removed: &mut Vec<GameId>) -> Vec<PathBuf> {
+        let Some(loaded_source) = self.sources.remove(source) else {
+            return Vec::new();
+        };
+        for hash in loaded_source.skipped {
+            if let Some(sources) = self.skipped.get_mut(&hash) {
+                if let Some(index) = sources.iter().position(|skipping| skipping == source) {
+                    sources.swap_remove(index);
+                }
+                if sources.is_empty() {
+                    self.skipped.remove(&hash);
+                }
+            }
+        }
+        let mut skipping = Vec::new();
+        for (id, hash) in loaded_source.games {
+            if let Some(files) = self.hashes.get_mut(&hash) {
+                files.retain(|file| file.source != source);
+                if files.is_empty() {
+                    self.hashes.remove(&hash);
+                }
+            }
+            skipping.extend(self.skipped.get(&hash).into_iter().flatten().cloned());
+            self.ids.remove(&id);
+            removed.push(id);
+        }
+        skipping
+    }
+
+    /// The loaded game with exactly the content of the file at `path` in `source`.
+    fn identical(&self, hash: u64, source: &Path, path: &Path, contents: &mut Contents) -> Option<GameId> {
+        let candidates = self.hashes.get(&hash)?;
+        // the hash alone could collide
+        let data = contents.get(source, path)?.to_owned();
+        candidates
+            .iter()
+            .find(|file| contents.get(&file.source, &file.path) == Some(&data[..]))
+            .map(|file| file.id.clone())
+    }
+
+    /// Updates the games after the files or directories at `paths` were created, changed or removed.
+    ///
+    /// Paths that are not game sources are ignored, and sources with the same size and modification time as before
+    /// are not parsed again. Duplicates and conflicts are found like in [`load`]. When a file is removed, the sources
+    /// with skipped duplicates of it are parsed again, so a duplicate is loaded in its place.
+    pub fn update<P: AsRef<Path>>(&mut self, paths: &[P]) -> Update {
+        let mut update = Update::default();
+        let mut found = BTreeSet::new();
+        let mut skipping = Vec::new();
+        for path in paths {
+            let path = path.as_ref();
+            let sources = if fs::symlink_metadata(path).is_ok() {
+                match sources(path) {
+                    Ok(sources) => sources,
+                    Err(err) => {
+                        update.rejected.push((path.to_owned(), vec![err]));
+                        continue;
+                    }
+                }
+            } else {
+                Vec::new()
+            };
+            let removed = self
+                .sources
+                .keys()
+                .filter(|source| source.starts_with(path) && !sources.contains(source))
+                .cloned()
+                .collect::<Vec<_>>();
+            for source in removed {
+                skipping.extend(self.remove(&source, &mut update.removed));
+                update.updated.push(source);
+            }
+            found.extend(sources);
+        }
+
+        let mut changed = found
+            .into_iter()
+            .filter(|source| {
+                let old_version = self.sources.get(source).and_then(|source| source.version.as_ref());
+                old_version.is_none() || old_version != SourceVersion::of(source).ok().as_ref()
+            })
+            .collect::<BTreeSet<_>>();
+        // the sources with skipped duplicates of removed games are parsed again as well, which can remove more games
+        let mut queue = changed.iter().cloned().collect::<Vec<_>>();
+        loop {
+            for source in skipping.drain(..) {
+                if self.sources.contains_key(&source) && changed.insert(source.clone()) {
+                    queue.push(source);
+                }
+            }
+            let Some(source) = queue.pop() else {
+                break;
+            };
+            skipping = self.remove(&source, &mut update.removed);
+        }
+
+        let parsed = changed
+            .into_iter()
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map(|source| {
+                let version = SourceVersion::of(&source).ok();
+                let parsed = parse_source(&source);
+                (source, version, parsed)
+            })
+            .collect::<Vec<_>>();
+        let mut files = Vec::new();
+        for (source, version, parsed) in parsed {
+            let (source_files, errors) = parsed.unwrap_or_else(|err| (Vec::new(), vec![err]));
+            let version = version.filter(|_| errors.is_empty());
+            self.sources.insert(
+                source.clone(),
+                LoadedSource {
+                    version,
+                    ..LoadedSource::default()
+                },
+            );
+            files.extend(source_files.into_iter().map(|file| (source.clone(), file)));
+            if !errors.is_empty() {
+                update.rejected.push((source.clone(), errors));
+            }
+            update.updated.push(source);
+        }
+        // like in `load`, so the same duplicates are skipped
+        files.sort_by(|(_, file1), (_, file2)| file1.path.cmp(&file2.path));
+        let mut contents = Contents::default();
+        for (source, ParsedFile { path, hash, game }) in files {
+            match self.insert(&source, &path, hash, &game, &mut contents) {
+                Inserted::Duplicate(original) => update.duplicates.push((path, original)),
+                Inserted::Game { id, conflict } => {
+                    if let Some(conflict) = conflict {
+                        update.conflicts.push((id.clone(), conflict));
+                    }
+                    update.added.push((id, game));
+                }
+            }
+        }
+        update
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn load_compressed_and_archived() {
+        let dir = TempDir::new("load");
+        fs::create_dir_all(dir.join("2021/08")).unwrap();
+        let names = ["1629228969.json", "1629231388.json", "1629235989.json", "1630089535.json"];
+        let data = names.map(|name| fs::read(Path::new("../test_data").join(name)).unwrap());
+
+        fs::write(dir.join("2021/08").join(names[0]), &data[0]).unwrap();
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gzip.write_all(&data[1]).unwrap();
+        fs::write(dir.join("2021").join(format!("{}.gz", names[1])), gzip.finish().unwrap()).unwrap();
+
+        let mut tar = tar::Builder::new(zstd::Encoder::new(Vec::new(), 0).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data[2].len() as u64);
+        header.set_cksum();
+        tar.append_data(&mut header, format!("games/{}", names[2]), &data[2][..]).unwrap();
+        fs::write(dir.join("archive.tar.zst"), tar.into_inner().unwrap().finish().unwrap()).unwrap();
+
+        let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        zip.start_file(names[3], zip::write::FileOptions::default()).unwrap();
+        zip.write_all(&data[3]).unwrap();
+        // a duplicate that is skipped
+        zip.start_file("copy.json", zip::write::FileOptions::default()).unwrap();
+        zip.write_all(&data[3]).unwrap();
+        fs::write(dir.join("archive.zip"), zip.finish().unwrap().into_inner()).unwrap();
+        fs::write(dir.join("notes.txt"), "not a game").unwrap();
+
+        let games = load(&dir).unwrap();
+        let file_names = games.games.keys().map(|id| id.file_name.as_str()).collect::<Vec<_>>();
+        assert_eq!(file_names, ["1629228969.json", "1629231388.json.gz", "1629235989.json", "1630089535.json"]);
+        assert_eq!(games.duplicates.len(), 1);
+        assert!(games.duplicates[0].0.ends_with("archive.zip/copy.json"));
+    }
+
+    #[test]
+    fn cache_detects_changes() {
+        let dir = TempDir::new("cache");
+        let (data_path, cache_path) = (dir.join("data"), dir.join("cache.bin"));
+        fs::create_dir_all(&data_path).unwrap();
+        let names = ["1629228969.json", "1629231388.json", "1629235989.json"];
+        let data = names.map(|name| fs::read(Path::new("../test_data").join(name)).unwrap());
+        fs::write(data_path.join("a.json"), &data[0]).unwrap();
+        fs::write(data_path.join("b.json"), &data[1]).unwrap();
+        let round_dates = |games: Games| games.games.keys().map(|id| id.round_date).collect::<Vec<_>>();
+
+        let games = load_cached(&data_path, &cache_path).unwrap();
+        assert_eq!(round_dates(games), [1629228969, 1629231388]);
+        assert!(cache_path.exists());
+        // the files were just written, so they are not trusted yet
+        assert!(Cache::read(&cache_path)
+            .take(&data_path.join("a.json"), &SourceVersion::of(&data_path.join("a.json")).unwrap())
+            .is_none());
+
+        // pretend the cache was written long after the files, and change its content to see that it is used
+        let mut cache = Cache::read(&cache_path);
+        cache.read += Duration::from_secs(60);
+        cache.sources.get_mut(&data_path.join("b.json")).unwrap().1[0].game.round_info.round_date = 1;
+        cache.write(&cache_path).unwrap();
+        assert_eq!(round_dates(load_cached(&data_path, &cache_path).unwrap()), [1, 1629228969]);
+
+        fs::write(data_path.join("b.json"), &data[2]).unwrap();
+        fs::remove_file(data_path.join("a.json")).unwrap();
+        assert_eq!(round_dates(load_cached(&data_path, &cache_path).unwrap()), [1629235989]);
+        assert_eq!(Cache::read(&cache_path).sources.len(), 1);
+
+        fs::write(&cache_path, "corrupt").unwrap();
+        assert_eq!(round_dates(load_cached(&data_path, &cache_path).unwrap()), [1629235989]);
+    }
+
+    #[test]
+    fn fail_or_collect_errors() {
+        let dir = TempDir::new("errors");
+        let data = fs::read_to_string("../test_data/1629228969.json").unwrap();
+        fs::write(dir.join("a.json"), &data).unwrap();
+        // a file that is still being written
+        fs::write(dir.join("b.json"), &data[..data.len() / 2]).unwrap();
+        let mut game = serde_json::from_str::<serde_json::Value>(&data).unwrap();
+        game["RoundInfo"]["startingLocations"]["1"] = 1000.into();
+        fs::write(dir.join("c.json"), game.to_string()).unwrap();
+
+        let error = load(&dir).err().unwrap();
+        assert!(
+            matches!(&error, LoadError::Parse { path, line, .. } if path.ends_with("b.json") && *line > 1),
+            "{:?}",
+            error
+        );
+        let message = error.to_string();
+        let cause = error.source().unwrap().to_string();
+        assert_eq!(ErrorChain(&error).to_string(), format!("{}: {}", message, cause));
+        assert_eq!(format!("{:#}", ErrorChain(&error)), format!("{}\n{}", message, cause));
+        let games = load_with(
+            &dir,
+            &LoadOptions {
+                on_error: OnError::Collect,
+                ..LoadOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(games.games.len(), 1);
+        assert_eq!(games.rejected.len(), 2);
+        assert!(matches!(&games.rejected[1], LoadError::Invalid { path, .. } if path.ends_with("c.json")));
+        assert!(matches!(load(dir.join("missing")), Err(LoadError::Io { .. })));
+    }
+
+    #[test]
+    fn duplicates_and_conflicts() {
+        let dir = TempDir::new("duplicates");
+        let data = fs::read_to_string("../test_data/1629228969.json").unwrap();
+        fs::write(dir.join("a.json"), &data).unwrap();
+        fs::write(dir.join("b.json"), &data).unwrap();
+        // the same round with a different content
+        let mut game = serde_json::from_str::<serde_json::Value>(&data).unwrap();
+        game["RoundInfo"]["roundLength"] = 1.0.into();
+        fs::write(dir.join("c.json"), game.to_string()).unwrap();
+
+        let games = load(&dir).unwrap();
+        let file_names = games.games.keys().map(|id| id.file_name.as_str()).collect::<Vec<_>>();
+        assert_eq!(file_names, ["a.json", "c.json"]);
+        assert_eq!(games.duplicates.len(), 1);
+        assert_eq!(games.duplicates[0].0, dir.join("b.json"));
+        assert_eq!(games.duplicates[0].1.file_name, "a.json");
+        assert_eq!(games.conflicts.len(), 1);
+        let (game, conflict) = &games.conflicts[0];
+        assert_eq!((game.file_name.as_str(), conflict.file_name.as_str()), ("c.json", "a.json"));
+    }
+
+    #[test]
+    fn update_changed_sources() {
+        let dir = TempDir::new("update");
+        let names = ["1629228969.json", "1629231388.json", "1629235989.json", "1630089535.json"];
+        let data = names.map(|name| fs::read(Path::new("../test_data").join(name)).unwrap());
+        fs::write(dir.join("a.json"), &data[0]).unwrap();
+        fs::write(dir.join("b.json"), &data[1]).unwrap();
+        let mut sources = load(&dir).unwrap().sources;
+        let round_dates = |ids: &[GameId]| ids.iter().map(|id| id.round_date).collect::<Vec<_>>();
+
+        let update = sources.update(&[&dir]);
+        assert!(update.added.is_empty() && update.removed.is_empty() && update.updated.is_empty());
+
+        fs::write(dir.join("b.json"), &data[2]).unwrap();
+        fs::write(dir.join("c.json"), &data[3]).unwrap();
+        // a duplicate and a file that is still being written
+        fs::write(dir.join("d.json"), &data[0]).unwrap();
+        fs::write(dir.join("e.json"), &data[1][..100]).unwrap();
+        let update = sources.update(&["b.json", "c.json", "d.json", "e.json"].map(|name| dir.join(name)));
+        assert_eq!(round_dates(&update.removed), [1629231388]);
+        let added = update.added.into_iter().map(|(id, _)| id).collect::<Vec<_>>();
+        assert_eq!(round_dates(&added), [1629235989, 1630089535]);
+        assert_eq!(update.rejected.len(), 1);
+        assert_eq!(update.rejected[0].0, dir.join("e.json"));
+        assert_eq!(update.duplicates.len(), 1);
+        assert_eq!(update.duplicates[0].0, dir.join("d.json"));
+
+        // the duplicate is loaded in place of the removed file
+        fs::remove_file(dir.join("a.json")).unwrap();
+        fs::write(dir.join("e.json"), &data[1]).unwrap();
+        let update = sources.update(&[&dir]);
+        assert_eq!(round_dates(&update.removed), [1629228969]);
+        let added = update.added.into_iter().map(|(id, _)| id).collect::<Vec<_>>();
+        assert_eq!(round_dates(&added), [1629228969, 1629231388]);
+        assert_eq!(added[0].file_name, "d.json");
+        assert!(update.rejected.is_empty() && update.duplicates.is_empty() && update.conflicts.is_empty());
+
+        // the same round with a different content
+        let mut game = serde_json::from_slice::<serde_json::Value>(&data[0]).unwrap();
+        game["RoundInfo"]["roundLength"] = 1.0.into();
+        fs::write(dir.join("f.json"), game.to_string()).unwrap();
+        let update = sources.update(&[dir.join("f.json")]);
+        assert_eq!(update.conflicts.len(), 1);
+        let (game, conflict) = &update.conflicts[0];
+        assert_eq!((game.file_name.as_str(), conflict.file_name.as_str()), ("f.json", "d.json"));
+    }
+}
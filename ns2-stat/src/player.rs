@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::input_types::{GameStats, PlayerClass, Team, WinningTeam};
+use crate::prediction::smoothed_win_rate;
+use crate::{summarize_game, GameId, PlayerSummary, Stat};
+
+/// Finds the player name that matches `query` best.
+///
+/// An exact match is preferred, then a case-insensitive match, then names that contain the query when ignoring case
+/// and everything except letters and digits (e.g. clan tags), and finally names that differ by a few characters.
+/// If there is no unique match, the candidates are returned as the error, sorted by name.
+pub fn find_player<'a, I: IntoIterator<Item = &'a String>>(names: I, query: &str) -> Result<&'a str, Vec<&'a str>> {
+    let names = names.into_iter().map(|name| name.as_str()).collect::<Vec<_>>();
+    if let Some(name) = names.iter().find(|&&name| name == query) {
+        return Ok(name);
+    }
+    let unique = |mut candidates: Vec<&'a str>| {
+        candidates.sort_unstable();
+        match candidates[..] {
+            [name] => Ok(name),
+            _ => Err(candidates),
+        }
+    };
+
+    let lowercase = query.to_lowercase();
+    let candidates = names.iter().copied().filter(|name| name.to_lowercase() == lowercase).collect::<Vec<_>>();
+    if !candidates.is_empty() {
+        return unique(candidates);
+    }
+    let normalized = normalize(query);
+    if normalized.is_empty() {
+        return Err(Vec::new());
+    }
+    let candidates = names.iter().copied().filter(|name| normalize(name).contains(&normalized)).collect::<Vec<_>>();
+    if !candidates.is_empty() {
+        return unique(candidates);
+    }
+    let max_distance = normalized.chars().count() / 4;
+    let distances = names.iter().map(|&name| (name, edit_distance(&normalize(name), &normalized)));
+    let Some(min_distance) = distances
+        .clone()
+        .map(|(_, distance)| distance)
+        .min()
+        .filter(|&distance| distance <= max_distance)
+    else {
+        return Err(Vec::new());
+    };
+    unique(distances.filter(|&(_, distance)| distance == min_distance).map(|(name, _)| name).collect())
+}
+
+fn normalize(name: &str) -> String {
+    name.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// The Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = diagonal + (ca != cb) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// One game from the perspective of a player.
+#[derive(Debug, Serialize)]
+pub struct PlayerGame {
+    pub id: GameId,
+    /// The round date in Unix time.
+    pub round_date: u32,
+    pub map_name: String,
+    /// The round length in seconds.
+    pub round_length: f32,
+    pub team: Team,
+    pub won: bool,
+    pub commander: bool,
+    #[serde(flatten)]
+    pub stats: PlayerSummary,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MapRecord {
+    pub map_name: String,
+    pub games: u32,
+    pub wins: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WeaponRecord {
+    pub weapon: String,
+    pub kills: u32,
+    pub hits: u32,
+    pub misses: u32,
+    pub player_damage: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LifeformRecord {
+    pub lifeform: PlayerClass,
+    /// The total time played as this lifeform in seconds.
+    pub time: f32,
+}
+
+/// The performance over some games.
+#[derive(Debug, Serialize)]
+pub struct Form {
+    pub games: u32,
+    pub wins: u32,
+    /// `None` without deaths.
+    pub kd: Option<f32>,
+    /// The average score per second of round length, as in [`User::average_score`](crate::User::average_score).
+    /// `None` without games.
+    pub average_score: Option<f32>,
+}
+
+/// Everything about one player that goes beyond the totals in [`User`](crate::User).
+#[derive(Debug, Serialize)]
+pub struct PlayerProfile {
+    pub name: String,
+    /// The games of the player in chronological order.
+    pub games: Vec<PlayerGame>,
+    pub commander_games: Stat<u32>,
+    pub commander_wins: Stat<u32>,
+    /// The maps, sorted from the best to the worst win rate. Win rates are smoothed, so maps with few games are
+    /// closer to the middle.
+    pub maps: Vec<MapRecord>,
+    /// The weapons, sorted by kills.
+    pub weapons: Vec<WeaponRecord>,
+    /// The alien lifeforms, sorted by time played.
+    pub lifeforms: Vec<LifeformRecord>,
+}
+
+impl PlayerProfile {
+    /// Computes the profile of the player named `name`. Returns `None` if the player did not play in any game.
+    pub fn compute<'a, I: Iterator<Item = (&'a GameId, &'a GameStats)>>(name: &str, games: I) -> Option<Self> {
+        let mut games = games.collect::<Vec<_>>();
+        games.sort_by_key(|&(id, _)| id);
+
+        let mut player_games = Vec::new();
+        let mut commander_games = Stat::default();
+        let mut commander_wins = Stat::default();
+        let mut maps = HashMap::<&str, MapRecord>::new();
+        let mut weapons = HashMap::<&str, WeaponRecord>::new();
+        let mut lifeforms = HashMap::new();
+        for (id, game) in games {
+            let Some(player_stat) = game.player_stats.values().find(|player_stat| player_stat.player_name == name) else {
+                continue;
+            };
+            let mut summary = summarize_game(id, game);
+            let (team, team_summary) = if player_stat.marines.time_played > player_stat.aliens.time_played {
+                (Team::Marines, &mut summary.marines)
+            } else {
+                (Team::Aliens, &mut summary.aliens)
+            };
+            let won = match game.round_info.winning_team {
+                WinningTeam::Marines => team == Team::Marines,
+                WinningTeam::Aliens => team == Team::Aliens,
+                WinningTeam::None => false,
+            };
+            let commander = team_summary.is_commander(name);
+            if commander {
+                commander_games.add(team, 1);
+                commander_wins.add(team, won as u32);
+            }
+
+            let map = maps.entry(&game.round_info.map_name).or_insert_with(|| MapRecord {
+                map_name: game.round_info.map_name.clone(),
+                games: 0,
+                wins: 0,
+            });
+            map.games += 1;
+            map.wins += won as u32;
+            for (weapon_name, weapon) in &player_stat.weapons {
+                let record = weapons.entry(weapon_name).or_insert_with(|| WeaponRecord {
+                    weapon: weapon_name.clone(),
+                    kills: 0,
+                    hits: 0,
+                    misses: 0,
+                    player_damage: 0.0,
+                });
+                record.kills += weapon.kills;
+                record.hits += weapon.hits;
+                record.misses += weapon.misses;
+                record.player_damage += weapon.player_damage;
+            }
+            for status in &player_stat.status {
+                if let PlayerClass::Skulk | PlayerClass::Gorge | PlayerClass::Lerk | PlayerClass::Fade | PlayerClass::Onos = status.status_id {
+                    *lifeforms.entry(status.status_id).or_insert(0.0) += status.class_time;
+                }
+            }
+
+            player_games.push(PlayerGame {
+                stats: team_summary.players.remove(name)?,
+                id: summary.id,
+                round_date: summary.round_date,
+                map_name: summary.map_name,
+                round_length: summary.round_length,
+                team,
+                won,
+                commander,
+            });
+        }
+        if player_games.is_empty() {
+            return None;
+        }
+
+        let mut maps = maps.into_values().collect::<Vec<_>>();
+        maps.sort_by(|map1, map2| {
+            let win_rate = |map: &MapRecord| smoothed_win_rate(map.wins, map.games);
+            f32::total_cmp(&win_rate(map2), &win_rate(map1)).then_with(|| map1.map_name.cmp(&map2.map_name))
+        });
+        let mut weapons = weapons.into_values().collect::<Vec<_>>();
+        weapons.sort_by_key(|weapon| std::cmp::Reverse((weapon.kills, weapon.hits)));
+        let mut lifeforms = lifeforms
+            .into_iter()
+            .map(|(lifeform, time)| LifeformRecord { lifeform, time })
+            .collect::<Vec<_>>();
+        lifeforms.sort_by(|lifeform1, lifeform2| f32::total_cmp(&lifeform2.time, &lifeform1.time));
+
+        Some(Self {
+            name: name.to_owned(),
+            games: player_games,
+            commander_games,
+            commander_wins,
+            maps,
+            weapons,
+            lifeforms,
+        })
+    }
+
+    /// The `count` latest games, the latest first.
+    pub fn recent_games(&self, count: usize) -> impl Iterator<Item = &PlayerGame> {
+        self.games.iter().rev().take(count)
+    }
+
+    /// The performance over the `count` latest games.
+    pub fn form(&self, count: usize) -> Form {
+        Form::compute(&self.games[self.games.len().saturating_sub(count)..])
+    }
+
+    /// The performance over every `window` consecutive games, oldest first. Empty if there are fewer games than `window`.
+    pub fn rolling_form(&self, window: usize) -> Vec<Form> {
+        self.games.windows(window.max(1)).map(Form::compute).collect()
+    }
+}
+
+impl Form {
+    fn compute(games: &[PlayerGame]) -> Self {
+        let (kills, deaths) = games
+            .iter()
+            .fold((0, 0), |(kills, deaths), game| (kills + game.stats.kills, deaths + game.stats.deaths));
+        Form {
+            games: games.len() as u32,
+            wins: games.iter().filter(|game| game.won).count() as u32,
+            kd: (deaths > 0).then(|| kills as f32 / deaths as f32),
+            average_score: (!games.is_empty()).then(|| games.iter().map(|game| game.stats.score as f32 / game.round_length).sum::<f32>() / games.len() as f32),
+        }
+    }
+
+    /// The win rate in percent, `None` without games.
+    pub fn win_rate(&self) -> Option<f32> {
+        (self.games > 0).then(|| self.wins as f32 * 100.0 / self.games as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_player_fuzzy() {
+        let names = ["[TAG] Kid Ilias", "konsumlamm", "Konsumlamm2", "Hiraeth"].map(|name| name.to_owned());
+        assert_eq!(find_player(&names, "konsumlamm"), Ok("konsumlamm"));
+        assert_eq!(find_player(&names, "HIRAETH"), Ok("Hiraeth"));
+        assert_eq!(find_player(&names, "kidilias"), Ok("[TAG] Kid Ilias"));
+        assert_eq!(find_player(&names, "konsum"), Err(vec!["Konsumlamm2", "konsumlamm"]));
+        assert_eq!(find_player(&names, "Hiraehth"), Ok("Hiraeth"));
+        assert_eq!(find_player(&names, "xyz"), Err(vec![]));
+    }
+
+    #[test]
+    fn form_without_games_or_deaths() {
+        let games = crate::load::load("../test_data").unwrap().games;
+        let name = &games.values().next().unwrap().player_stats.values().next().unwrap().player_name;
+        let mut profile = PlayerProfile::compute(name, games.iter()).unwrap();
+        let form = profile.form(0);
+        assert_eq!((form.games, form.win_rate(), form.kd, form.average_score), (0, None, None, None));
+
+        profile.games.iter_mut().for_each(|game| game.stats.deaths = 0);
+        let form = profile.form(1);
+        assert_eq!(form.kd, None);
+        assert!(form.win_rate().is_some() && form.average_score.is_some());
+    }
+}
@@ -0,0 +1,363 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::hive_skill::apply_offset;
+use crate::input_types::{GameStats, Team, WinningTeam};
+use crate::{get_commander, Map, Stat};
+
+/// The number of features, including the bias.
+const FEATURES: usize = 5;
+
+/// The teams for a game that is about to be played.
+#[derive(Clone, Debug, Default)]
+pub struct Roster {
+    pub marines: Vec<String>,
+    pub aliens: Vec<String>,
+    pub marine_commander: Option<String>,
+    pub alien_commander: Option<String>,
+    pub map_name: Option<String>,
+}
+
+impl Roster {
+    /// The roster of a game that was already played.
+    pub fn from_game(game: &GameStats) -> Self {
+        let mut roster = Roster {
+            marine_commander: get_commander(Team::Marines, &game.player_stats).map(|name| name.to_owned()),
+            alien_commander: get_commander(Team::Aliens, &game.player_stats).map(|name| name.to_owned()),
+            map_name: Some(game.round_info.map_name.clone()),
+            ..Default::default()
+        };
+        for player_stat in game.player_stats.values() {
+            if player_stat.marines.time_played > player_stat.aliens.time_played {
+                roster.marines.push(player_stat.player_name.clone());
+            } else {
+                roster.aliens.push(player_stat.player_name.clone());
+            }
+        }
+        roster
+    }
+}
+
+#[derive(Default)]
+struct PlayerRecord {
+    games: Stat<u32>,
+    wins: Stat<u32>,
+    commander_games: Stat<u32>,
+    commander_wins: Stat<u32>,
+    /// The latest known hive skill, with the offsets applied.
+    hive_skill: Option<Stat<u32>>,
+}
+
+/// A win rate with one win and one loss added, so players with few games are close to 50%.
+pub(crate) fn smoothed_win_rate(wins: u32, games: u32) -> f32 {
+    (wins + 1) as f32 / (games + 2) as f32
+}
+
+fn side<T: Copy>(stat: &Stat<T>, team: Team) -> T {
+    match team {
+        Team::Marines => stat.marines,
+        Team::Aliens => stat.aliens,
+    }
+}
+
+/// The aggregated history that the features of a game are computed from.
+#[derive(Default)]
+struct PredictionContext {
+    players: HashMap<String, PlayerRecord>,
+    maps: HashMap<String, Map>,
+}
+
+impl PredictionContext {
+    /// Computes the features from the marines' perspective.
+    fn features(&self, roster: &Roster) -> [f32; FEATURES] {
+        let mean = |values: Vec<f32>| (!values.is_empty()).then(|| values.iter().sum::<f32>() / values.len() as f32);
+        let team_skill = |players: &[String], team| {
+            mean(
+                players
+                    .iter()
+                    .filter_map(|name| Some(side(&self.players.get(name)?.hive_skill?, team) as f32))
+                    .collect(),
+            )
+        };
+        let team_win_rate = |players: &[String], team| {
+            let win_rates = players.iter().map(|name| match self.players.get(name) {
+                Some(record) => smoothed_win_rate(side(&record.wins, team), side(&record.games, team)),
+                None => 0.5,
+            });
+            mean(win_rates.collect()).unwrap_or(0.5)
+        };
+        let commander_win_rate = |commander: &Option<String>, team| match commander.as_ref().and_then(|name| self.players.get(name)) {
+            Some(record) => smoothed_win_rate(side(&record.commander_wins, team), side(&record.commander_games, team)),
+            None => 0.5,
+        };
+        let map_win_rate = match roster.map_name.as_ref().and_then(|name| self.maps.get(name)) {
+            Some(map) => smoothed_win_rate(map.marine_wins, map.marine_wins + map.alien_wins),
+            None => 0.5,
+        };
+
+        let skill_difference = match (team_skill(&roster.marines, Team::Marines), team_skill(&roster.aliens, Team::Aliens)) {
+            (Some(marines), Some(aliens)) => (marines - aliens) / 1000.0,
+            _ => 0.0,
+        };
+        [
+            1.0,
+            skill_difference,
+            team_win_rate(&roster.marines, Team::Marines) - team_win_rate(&roster.aliens, Team::Aliens),
+            commander_win_rate(&roster.marine_commander, Team::Marines) - commander_win_rate(&roster.alien_commander, Team::Aliens),
+            map_win_rate - 0.5,
+        ]
+    }
+
+    fn update(&mut self, game: &GameStats) {
+        let roster = Roster::from_game(game);
+        let winner = match game.round_info.winning_team {
+            WinningTeam::Marines => Some(Team::Marines),
+            WinningTeam::Aliens => Some(Team::Aliens),
+            WinningTeam::None => None,
+        };
+        for player_stat in game.player_stats.values() {
+            let record = match self.players.get_mut(&player_stat.player_name) {
+                Some(record) => record,
+                None => self.players.entry(player_stat.player_name.clone()).or_default(),
+            };
+            if player_stat.hive_skill > 0 {
+                record.hive_skill = Some(apply_offset(player_stat.hive_skill, player_stat.player_skill_offset));
+            }
+        }
+        for (team, players, commander) in [
+            (Team::Marines, &roster.marines, &roster.marine_commander),
+            (Team::Aliens, &roster.aliens, &roster.alien_commander),
+        ] {
+            let won = (winner == Some(team)) as u32;
+            for name in players {
+                if let Some(record) = self.players.get_mut(name) {
+                    record.games.add(team, 1);
+                    record.wins.add(team, won);
+                }
+            }
+            if let Some(record) = commander.as_ref().and_then(|name| self.players.get_mut(name)) {
+                record.commander_games.add(team, 1);
+                record.commander_wins.add(team, won);
+            }
+        }
+
+        let map = match self.maps.get_mut(&game.round_info.map_name) {
+            Some(map) => map,
+            None => self.maps.entry(game.round_info.map_name.clone()).or_default(),
+        };
+        map.total_games += 1;
+        match winner {
+            Some(Team::Marines) => map.marine_wins += 1,
+            Some(Team::Aliens) => map.alien_wins += 1,
+            None => {}
+        }
+    }
+}
+
+/// The win probability of each team.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct WinPrediction {
+    pub marines: f32,
+    pub aliens: f32,
+}
+
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+fn dot(weights: &[f32; FEATURES], features: &[f32; FEATURES]) -> f32 {
+    weights.iter().zip(features).map(|(w, x)| w * x).sum()
+}
+
+/// Fits the weights of a logistic regression, where the label is `true` if the marines won.
+fn fit_weights(samples: &[([f32; FEATURES], bool)]) -> [f32; FEATURES] {
+    const ITERATIONS: usize = 1000;
+    const LEARNING_RATE: f32 = 0.5;
+    const REGULARIZATION: f32 = 0.01;
+
+    let mut weights = [0.0; FEATURES];
+    if samples.is_empty() {
+        return weights;
+    }
+    for _ in 0..ITERATIONS {
+        let mut gradient = [0.0; FEATURES];
+        for (features, marines_won) in samples {
+            let error = sigmoid(dot(&weights, features)) - *marines_won as u8 as f32;
+            for (g, x) in gradient.iter_mut().zip(features) {
+                *g += error * x;
+            }
+        }
+        for (i, (w, g)) in weights.iter_mut().zip(gradient).enumerate() {
+            // the bias is not regularized
+            let penalty = if i == 0 { 0.0 } else { REGULARIZATION * *w };
+            *w -= LEARNING_RATE * (g / samples.len() as f32 + penalty);
+        }
+    }
+    weights
+}
+
+/// Computes the features of each decided game from the games before it, in chronological order.
+fn samples<'a, I: Iterator<Item = &'a GameStats>>(games: I) -> (Vec<([f32; FEATURES], bool)>, PredictionContext) {
+    let mut games = games.collect::<Vec<_>>();
+    games.sort_by_key(|game| game.round_info.round_date);
+
+    let mut builder = WinModelBuilder::default();
+    for game in games {
+        builder.add_game(game);
+    }
+    (builder.samples, builder.context)
+}
+
+/// Fits a [`WinModel`] on games that are added one at a time, for games that are not all in memory at once.
+#[derive(Default)]
+pub struct WinModelBuilder {
+    samples: Vec<([f32; FEATURES], bool)>,
+    context: PredictionContext,
+}
+
+impl WinModelBuilder {
+    /// Adds a game after the games that were added before, so the games have to be added in chronological order.
+    pub fn add_game(&mut self, game: &GameStats) {
+        let marines_won = match game.round_info.winning_team {
+            WinningTeam::Marines => Some(true),
+            WinningTeam::Aliens => Some(false),
+            WinningTeam::None => None,
+        };
+        if let Some(marines_won) = marines_won {
+            self.samples.push((self.context.features(&Roster::from_game(game)), marines_won));
+        }
+        self.context.update(game);
+    }
+
+    pub fn fit(self) -> WinModel {
+        WinModel {
+            weights: fit_weights(&self.samples),
+            context: self.context,
+        }
+    }
+}
+
+/// A model that predicts the outcome of a game from the team composition.
+///
+/// The features are the differences of the teams' hive skills, per-side win rates and commander win rates,
+/// as well as the marine win rate of the map.
+pub struct WinModel {
+    weights: [f32; FEATURES],
+    context: PredictionContext,
+}
+
+impl WinModel {
+    /// Fits the model on the given games.
+    pub fn fit<'a, I: Iterator<Item = &'a GameStats>>(games: I) -> Self {
+        let (samples, context) = samples(games);
+        Self {
+            weights: fit_weights(&samples),
+            context,
+        }
+    }
+
+    pub fn predict(&self, roster: &Roster) -> WinPrediction {
+        let marines = sigmoid(dot(&self.weights, &self.context.features(roster)));
+        WinPrediction {
+            marines,
+            aliens: 1.0 - marines,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CalibrationBin {
+    /// The mean predicted marine win probability in this bin.
+    pub predicted: f32,
+    /// The fraction of games in this bin that the marines won.
+    pub observed: f32,
+    pub games: u32,
+}
+
+/// The result of evaluating the model on games it was not fitted on.
+#[derive(Debug, Serialize)]
+pub struct Backtest {
+    /// The number of games the model was fitted on.
+    pub training_games: u32,
+    /// The number of games the model was evaluated on.
+    pub test_games: u32,
+    /// The fraction of test games where the team with the higher predicted win probability won.
+    pub accuracy: f32,
+    /// The mean squared error of the predicted marine win probability.
+    pub brier_score: f32,
+    /// The test games grouped by the predicted marine win probability in steps of 10%. Empty bins are omitted.
+    pub calibration: Vec<CalibrationBin>,
+}
+
+impl Backtest {
+    /// Fits the model on the oldest `train_fraction` of the games and evaluates it on the rest.
+    /// The features of every game are computed only from the games before it.
+    ///
+    /// Returns `None` if there are no games to fit the model on or no games to evaluate it on.
+    pub fn run<'a, I: Iterator<Item = &'a GameStats>>(games: I, train_fraction: f32) -> Option<Self> {
+        let (samples, _) = samples(games);
+        let split = ((samples.len() as f32 * train_fraction) as usize).min(samples.len());
+        let (training, test) = samples.split_at(split);
+        if training.is_empty() || test.is_empty() {
+            return None;
+        }
+        let weights = fit_weights(training);
+
+        let mut correct = 0;
+        let mut squared_error = 0.0;
+        let mut bins = [(0.0, 0, 0); 10];
+        for (features, marines_won) in test {
+            let marines = sigmoid(dot(&weights, features));
+            if (marines > 0.5) == *marines_won {
+                correct += 1;
+            }
+            squared_error += (marines - *marines_won as u8 as f32).powi(2);
+            let bin = &mut bins[((marines * 10.0) as usize).min(9)];
+            bin.0 += marines;
+            bin.1 += *marines_won as u32;
+            bin.2 += 1;
+        }
+
+        let test_games = test.len() as f32;
+        Some(Self {
+            training_games: training.len() as u32,
+            test_games: test.len() as u32,
+            accuracy: correct as f32 / test_games,
+            brier_score: squared_error / test_games,
+            calibration: bins
+                .into_iter()
+                .filter(|&(_, _, games)| games > 0)
+                .map(|(predicted, wins, games)| CalibrationBin {
+                    predicted: predicted / games as f32,
+                    observed: wins as f32 / games as f32,
+                    games,
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_weights_separable() {
+        let samples = [([1.0, 0.5, 0.0, 0.0, 0.0], true), ([1.0, -0.5, 0.0, 0.0, 0.0], false)].repeat(10);
+        let weights = fit_weights(&samples);
+        assert!(sigmoid(dot(&weights, &[1.0, 0.5, 0.0, 0.0, 0.0])) > 0.6);
+        assert!(sigmoid(dot(&weights, &[1.0, -0.5, 0.0, 0.0, 0.0])) < 0.4);
+    }
+
+    #[test]
+    fn backtest_needs_training_and_test_games() {
+        let games = crate::load::load("../test_data").unwrap().games;
+        let run = |train_fraction| Backtest::run(games.values(), train_fraction);
+        assert!(run(0.0).is_none());
+        assert!(run(1.0).is_none());
+        let backtest = run(0.5).unwrap();
+        assert!(backtest.training_games > 0 && backtest.test_games > 0);
+        assert!(backtest.accuracy.is_finite() && backtest.brier_score.is_finite());
+    }
+}
@@ -0,0 +1,44 @@
+//! Helpers for the tests of this crate, and of the other crates with the `testing` feature.
+
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::{env, fs, process};
+
+/// An empty directory in the temporary directory, which is removed with its contents when it is dropped, also when
+/// the test fails.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates a directory named after `name`, the process and a counter, so that tests running at the same time get
+    /// different directories.
+    pub fn new(name: &str) -> Self {
+        static COUNTER: AtomicU32 = AtomicU32::new(0);
+        let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = env::temp_dir().join(format!("ns2-stat-{}-{}-{}", name, process::id(), count));
+        // left over from a process with the same ID
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}